@@ -0,0 +1,522 @@
+//! Cookie，支持导入Netscape格式（`cookies.txt`）与浏览器扩展导出的JSON
+
+use crate::error::VideoSourceError;
+use crate::secret::Secret;
+use crate::source::Result;
+
+use reqwest::Url;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 单个Cookie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: Secret,
+    /// 小写且不带开头的`.`
+    pub domain: String,
+    /// 是否同时发送给子域名
+    pub include_subdomains: bool,
+    pub path: String,
+    /// 是否只通过HTTPS发送
+    pub secure: bool,
+    /// 过期时间（Unix时间戳，秒），`None`为会话Cookie
+    pub expires: Option<i64>,
+}
+
+impl Cookie {
+    /// 发送给`domain`及其子域名的会话Cookie
+    pub fn new(name: &str, value: &str, domain: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.into(),
+            domain: normalize_domain(domain),
+            include_subdomains: true,
+            path: "/".to_string(),
+            secure: false,
+            expires: None,
+        }
+    }
+
+    /// 解析响应头`Set-Cookie`，`url`为请求地址，没有`Domain`、`Path`时按请求地址确定
+    ///
+    /// `Max-Age`优先于`Expires`，没有名称时返回`None`
+    pub fn from_set_cookie(header: &str, url: &Url) -> Option<Self> {
+        let mut attributes = header.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = Self {
+            name: name.to_string(),
+            value: value.trim().into(),
+            domain: normalize_domain(url.host_str()?),
+            include_subdomains: false,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            if key.eq_ignore_ascii_case("Domain") && !value.is_empty() {
+                cookie.domain = normalize_domain(value);
+                cookie.include_subdomains = true;
+            } else if key.eq_ignore_ascii_case("Path") && value.starts_with('/') {
+                cookie.path = value.to_string();
+            } else if key.eq_ignore_ascii_case("Secure") {
+                cookie.secure = true;
+            } else if key.eq_ignore_ascii_case("Expires") {
+                if let Ok(expires) = httpdate::parse_http_date(value) {
+                    cookie.expires = Some(unix_time(expires));
+                }
+            } else if key.eq_ignore_ascii_case("Max-Age") {
+                max_age = value.parse::<i64>().ok();
+            }
+        }
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(now() + max_age.max(0));
+        }
+        Some(cookie)
+    }
+
+    /// 在`now`（Unix时间戳，秒）时是否已过期
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// 请求`url`时是否应发送，不检查是否过期
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        let path = url.path();
+        self.matches_host(&host)
+            && (!self.secure || url.scheme() == "https")
+            && (self.path == "/"
+                || path == self.path
+                || path.starts_with(&format!("{}/", self.path.trim_end_matches('/'))))
+    }
+
+    /// 是否属于`domain`或其子域名
+    pub fn belongs_to(&self, domain: &str) -> bool {
+        let domain = normalize_domain(domain);
+        self.domain == domain || self.domain.ends_with(&format!(".{}", domain))
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        host == self.domain
+            || (self.include_subdomains && host.ends_with(&format!(".{}", self.domain)))
+    }
+}
+
+/// 导入Cookie时的提示
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieWarning {
+    /// 缺少必需的Cookie
+    Missing(String),
+    /// 必需的Cookie已过期
+    Expired(String),
+}
+
+impl Display for CookieWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CookieWarning::Missing(name) => write!(f, "缺少Cookie: {}", name),
+            CookieWarning::Expired(name) => write!(f, "Cookie已过期: {}", name),
+        }
+    }
+}
+
+/// Cookie集合，按请求的域名发送对应的Cookie
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 解析请求头格式的Cookie（`a=1; b=2`），均发送给`domain`及其子域名
+    pub fn from_header(header: &str, domain: &str) -> Self {
+        let mut jar = Self::new();
+        for pair in header.split(';') {
+            if let Some((name, value)) = pair.split_once('=') {
+                let name = name.trim();
+                if !name.is_empty() {
+                    jar.insert(Cookie::new(name, value.trim(), domain));
+                }
+            }
+        }
+        jar
+    }
+
+    /// 解析Netscape格式的`cookies.txt`
+    ///
+    /// 每行为以制表符分隔的`域名 是否包含子域名 路径 仅HTTPS 过期时间 名称 值`，
+    /// `#HttpOnly_`开头的行为HttpOnly的Cookie，其他`#`开头的行为注释
+    pub fn from_netscape(text: &str) -> Result<Self> {
+        let mut jar = Self::new();
+        for (line, number) in text.lines().zip(1..) {
+            let line = line.trim_end_matches('\r');
+            let line = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => line,
+                None if line.starts_with('#') || line.trim().is_empty() => continue,
+                None => line,
+            };
+            let invalid = || VideoSourceError::InvalidCookie(format!("第{}行: {}", number, line));
+            let fields: Vec<_> = line.split('\t').collect();
+            let (domain, include_subdomains, path, secure, expires, name, value) = match fields[..]
+            {
+                [domain, include_subdomains, path, secure, expires, name, value] => (
+                    domain,
+                    include_subdomains,
+                    path,
+                    secure,
+                    expires,
+                    name,
+                    value,
+                ),
+                // 值为空时部分工具省略最后一列
+                [domain, include_subdomains, path, secure, expires, name] => {
+                    (domain, include_subdomains, path, secure, expires, name, "")
+                }
+                _ => return Err(invalid()),
+            };
+            let expires: i64 = expires.parse().map_err(|_| invalid())?;
+            jar.insert(Cookie {
+                name: name.to_string(),
+                value: value.into(),
+                domain: normalize_domain(domain),
+                include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE")
+                    || domain.starts_with('.'),
+                path: path.to_string(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                expires: Some(expires).filter(|&expires| expires > 0),
+            });
+        }
+        Ok(jar)
+    }
+
+    /// 输出为Netscape格式，可由`from_netscape`读取，会话Cookie的过期时间为0
+    pub fn to_netscape(&self) -> String {
+        let mut text = String::from("# Netscape HTTP Cookie File\n");
+        for cookie in &self.cookies {
+            let flag = |value: bool| if value { "TRUE" } else { "FALSE" };
+            text.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.include_subdomains { "." } else { "" },
+                cookie.domain,
+                flag(cookie.include_subdomains),
+                cookie.path,
+                flag(cookie.secure),
+                cookie.expires.unwrap_or_default(),
+                cookie.name,
+                cookie.value.expose()
+            ));
+        }
+        text
+    }
+
+    /// 解析浏览器扩展（如EditThisCookie、Cookie-Editor）导出的JSON数组
+    pub fn from_json(text: &str) -> Result<Self> {
+        let cookies: Vec<JsonCookie> = serde_json::from_str(text)
+            .map_err(|error| VideoSourceError::InvalidCookie(error.to_string()))?;
+        let mut jar = Self::new();
+        for cookie in cookies {
+            jar.insert(Cookie {
+                include_subdomains: !cookie.host_only.unwrap_or(false)
+                    || cookie.domain.starts_with('.'),
+                domain: normalize_domain(&cookie.domain),
+                expires: cookie
+                    .expiration_date
+                    .filter(|_| !cookie.session)
+                    .map(|expires| expires as i64),
+                name: cookie.name,
+                value: cookie.value.into(),
+                path: cookie.path.unwrap_or_else(|| "/".to_string()),
+                secure: cookie.secure,
+            });
+        }
+        Ok(jar)
+    }
+
+    /// 添加Cookie，替换名称、域名与路径都相同的Cookie
+    pub fn insert(&mut self, cookie: Cookie) {
+        match self.cookies.iter_mut().find(|old| {
+            old.name == cookie.name && old.domain == cookie.domain && old.path == cookie.path
+        }) {
+            Some(old) => *old = cookie,
+            None => self.cookies.push(cookie),
+        }
+    }
+
+    /// 名称为`name`的Cookie，有多个时返回最晚过期的
+    pub fn get(&self, name: &str) -> Option<&Cookie> {
+        self.cookies
+            .iter()
+            .filter(|cookie| cookie.name == name)
+            .max_by_key(|cookie| cookie.expires.unwrap_or(i64::MAX))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    /// 只保留属于`domain`及其子域名的Cookie
+    pub fn retain_domain(&mut self, domain: &str) {
+        self.cookies.retain(|cookie| cookie.belongs_to(domain));
+    }
+
+    /// 移除已过期的Cookie
+    pub fn remove_expired(&mut self) {
+        let now = now();
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+    }
+
+    /// 请求`url`时发送的`Cookie`请求头，没有对应的Cookie时为`None`
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let now = now();
+        let pairs: Vec<_> = self
+            .cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(url))
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value.expose()))
+            .collect();
+        if pairs.is_empty() {
+            None
+        } else {
+            Some(pairs.join("; "))
+        }
+    }
+
+    /// 全部Cookie的请求头格式
+    pub fn to_header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value.expose()))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// 检查`names`中的Cookie是否存在且未过期
+    pub fn check(&self, names: &[&str]) -> Vec<CookieWarning> {
+        let now = now();
+        names
+            .iter()
+            .filter_map(|&name| match self.get(name) {
+                None => Some(CookieWarning::Missing(name.to_string())),
+                Some(cookie) if cookie.is_expired(now) => {
+                    Some(CookieWarning::Expired(name.to_string()))
+                }
+                Some(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// 浏览器扩展导出的Cookie
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub host_only: Option<bool>,
+    pub path: Option<String>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub session: bool,
+    /// 过期时间（Unix时间戳，秒），可能带有小数
+    #[serde(alias = "expires")]
+    pub expiration_date: Option<f64>,
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim_start_matches('.').to_ascii_lowercase()
+}
+
+/// 请求路径中最后一个`/`之前的部分，即没有`Path`时的默认路径
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => url.path()[..end].to_string(),
+    }
+}
+
+fn now() -> i64 {
+    unix_time(SystemTime::now())
+}
+
+/// Unix时间戳（秒），早于1970年时为0
+fn unix_time(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cookie, CookieJar, CookieWarning};
+    use crate::error::VideoSourceError;
+    use reqwest::Url;
+
+    /// 2100年
+    const FUTURE: i64 = 4102444800;
+
+    #[test]
+    fn netscape_cookie_test() {
+        let text = format!(
+            "# Netscape HTTP Cookie File\n\
+             \n\
+             #HttpOnly_.bilibili.com\tTRUE\t/\tFALSE\t{0}\tSESSDATA\tabc%2C123\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t1600000000\tbili_jct\told\n\
+             www.bilibili.com\tFALSE\t/video\tTRUE\t0\tsession\t1\n\
+             .example.com\tTRUE\t/\tFALSE\t{0}\tother\t2\n",
+            FUTURE
+        );
+        let jar = CookieJar::from_netscape(&text).unwrap();
+        assert_eq!(jar.len(), 4);
+        let sessdata = jar.get("SESSDATA").unwrap();
+        assert_eq!(
+            (sessdata.domain.as_str(), sessdata.value.expose()),
+            ("bilibili.com", "abc%2C123")
+        );
+        assert!(sessdata.include_subdomains);
+        assert_eq!(jar.get("session").unwrap().expires, None);
+
+        // 按域名、路径、协议与过期时间选择
+        let header = |url: &str| jar.header_for(&Url::parse(url).unwrap());
+        assert_eq!(
+            header("https://api.bilibili.com/x/player/playurl").as_deref(),
+            Some("SESSDATA=abc%2C123")
+        );
+        assert_eq!(
+            header("https://www.bilibili.com/video/BV1").as_deref(),
+            Some("SESSDATA=abc%2C123; session=1")
+        );
+        assert_eq!(
+            header("http://www.bilibili.com/video/BV1").as_deref(),
+            Some("SESSDATA=abc%2C123")
+        );
+        assert_eq!(
+            header("https://www.bilibili.com/videos").as_deref(),
+            Some("SESSDATA=abc%2C123")
+        );
+        assert_eq!(header("https://bilibili.com.example.org/"), None);
+
+        let mut bilibili = jar.clone();
+        bilibili.retain_domain("bilibili.com");
+        assert_eq!(bilibili.len(), 3);
+        assert_eq!(
+            bilibili.check(&["SESSDATA", "bili_jct", "DedeUserID"]),
+            vec![
+                CookieWarning::Expired("bili_jct".to_string()),
+                CookieWarning::Missing("DedeUserID".to_string()),
+            ]
+        );
+        bilibili.remove_expired();
+        assert_eq!(bilibili.to_header(), "SESSDATA=abc%2C123; session=1");
+
+        // 输出后再读取不变
+        assert_eq!(CookieJar::from_netscape(&jar.to_netscape()).unwrap(), jar);
+
+        let result = CookieJar::from_netscape(".bilibili.com\tTRUE\t/\n");
+        assert!(matches!(result, Err(VideoSourceError::InvalidCookie(_))));
+    }
+
+    #[test]
+    fn set_cookie_test() {
+        let url =
+            Url::parse("https://passport.bilibili.com/x/passport-login/web/qrcode/poll").unwrap();
+        let cookie = Cookie::from_set_cookie(
+            "SESSDATA=abc%2C123; Path=/; Domain=.bilibili.com; \
+             Expires=Fri, 01 Jan 2100 00:00:00 GMT; HttpOnly; Secure",
+            &url,
+        )
+        .unwrap();
+        assert_eq!(
+            cookie,
+            Cookie {
+                name: "SESSDATA".to_string(),
+                value: "abc%2C123".into(),
+                domain: "bilibili.com".to_string(),
+                include_subdomains: true,
+                path: "/".to_string(),
+                secure: true,
+                expires: Some(FUTURE),
+            }
+        );
+
+        // 没有`Domain`时只发送给请求的域名，路径默认为请求路径的目录
+        let cookie = Cookie::from_set_cookie("bili_jct=def", &url).unwrap();
+        assert_eq!(cookie.domain, "passport.bilibili.com");
+        assert!(!cookie.include_subdomains);
+        assert_eq!(cookie.path, "/x/passport-login/web/qrcode");
+        assert_eq!(cookie.expires, None);
+
+        // `Max-Age`优先于`Expires`
+        let cookie = Cookie::from_set_cookie(
+            "a=1; Max-Age=0; Expires=Fri, 01 Jan 2100 00:00:00 GMT",
+            &url,
+        )
+        .unwrap();
+        assert!(cookie.is_expired(super::now()));
+
+        assert_eq!(Cookie::from_set_cookie("=1; Path=/", &url), None);
+        assert_eq!(Cookie::from_set_cookie("invalid", &url), None);
+    }
+
+    #[test]
+    fn json_cookie_test() {
+        let text = format!(
+            r#"[
+                {{"domain": ".bilibili.com", "hostOnly": false, "name": "SESSDATA", "value": "abc",
+                  "path": "/", "secure": true, "session": false, "expirationDate": {}.5}},
+                {{"domain": "www.bilibili.com", "hostOnly": true, "name": "bili_jct", "value": "def",
+                  "path": "/", "session": true}}
+            ]"#,
+            FUTURE
+        );
+        let jar = CookieJar::from_json(&text).unwrap();
+        let sessdata = jar.get("SESSDATA").unwrap();
+        assert_eq!(sessdata.expires, Some(FUTURE));
+        assert!(sessdata.secure);
+        let bili_jct = jar.get("bili_jct").unwrap();
+        assert!(!bili_jct.include_subdomains);
+        assert_eq!(bili_jct.expires, None);
+        assert_eq!(
+            jar.header_for(&Url::parse("https://api.bilibili.com/").unwrap())
+                .as_deref(),
+            Some("SESSDATA=abc")
+        );
+        assert!(jar.check(&["SESSDATA", "bili_jct"]).is_empty());
+
+        let jar = CookieJar::from_header("SESSDATA=abc; bili_jct = def;", "bilibili.com");
+        assert_eq!(jar.to_header(), "SESSDATA=abc; bili_jct=def");
+        assert_eq!(
+            jar.header_for(&Url::parse("https://example.com/").unwrap()),
+            None
+        );
+
+        let result = CookieJar::from_json("{}");
+        assert!(matches!(result, Err(VideoSourceError::InvalidCookie(_))));
+    }
+}
@@ -0,0 +1,361 @@
+//! 用口令加密保存在本地的各来源登录凭据
+
+use crate::cookie::{CookieJar, CookieWarning};
+use crate::error::VideoSourceError;
+use crate::secret::Secret;
+use crate::source::{Result, VideoSource};
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// 文件开头的标识与格式版本
+const MAGIC: &[u8; 5] = b"YGCR\x01";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// 凭据文件，格式为`标识 盐 nonce 密文`，密文解密后为来源名称到凭据的JSON
+///
+/// 密钥由口令经Argon2id派生，内容用ChaCha20-Poly1305加密，每次保存使用新的nonce。
+/// 密钥在释放时清零
+pub struct CredentialStore {
+    path: PathBuf,
+    salt: [u8; SALT_LEN],
+    key: Zeroizing<[u8; KEY_LEN]>,
+    /// 以`VideoSource::pretty_name`为键
+    credentials: BTreeMap<String, Credential>,
+}
+
+/// 保存的凭据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// `VideoSource::token`，用于不使用Cookie的来源
+    Token(Secret),
+    /// 带有域名、路径与过期时间的Cookie
+    Cookies(CookieJar),
+}
+
+/// 文件中的凭据，旧文件中的字符串为token，Cookie保存为Netscape格式。释放时清零
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(untagged)]
+enum StoredCredential {
+    Token(String),
+    Cookies { cookies: String },
+}
+
+impl CredentialStore {
+    /// 默认路径，即用户配置目录下的`youngoor/credentials`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("youngoor").join("credentials"))
+    }
+
+    /// 打开凭据文件，文件不存在时为空。口令错误或文件损坏时返回`InvalidCredential`
+    pub fn open(path: impl Into<PathBuf>, passphrase: &Secret) -> Result<Self> {
+        let path = path.into();
+        let data = match fs::read(&path) {
+            Ok(data) => Some(data),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        let data = match data {
+            Some(data) => data,
+            None => {
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let key = derive_key(passphrase, &salt)?;
+                return Ok(Self {
+                    path,
+                    salt,
+                    key,
+                    credentials: BTreeMap::new(),
+                });
+            }
+        };
+        let invalid = || VideoSourceError::InvalidCredential("口令错误或文件已损坏".to_string());
+        let body = data.strip_prefix(&MAGIC[..]).ok_or_else(|| {
+            VideoSourceError::InvalidCredential(format!("不是凭据文件: {}", path.display()))
+        })?;
+        if body.len() < SALT_LEN + NONCE_LEN {
+            return Err(invalid());
+        }
+        let (salt, body) = body.split_at(SALT_LEN);
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        let salt: [u8; SALT_LEN] = salt.try_into().map_err(|_| invalid())?;
+        let key = derive_key(passphrase, &salt)?;
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| invalid())?;
+        let stored: BTreeMap<String, StoredCredential> =
+            serde_json::from_slice(&plaintext).map_err(|_| invalid())?;
+        let mut credentials = BTreeMap::new();
+        for (source, credential) in stored {
+            let credential = match &credential {
+                StoredCredential::Token(token) => Credential::Token(token.as_str().into()),
+                StoredCredential::Cookies { cookies } => {
+                    Credential::Cookies(CookieJar::from_netscape(cookies)?)
+                }
+            };
+            credentials.insert(source, credential);
+        }
+        Ok(Self {
+            path,
+            salt,
+            key,
+            credentials,
+        })
+    }
+
+    /// 启动时调用：打开`path`，将保存的凭据设置到`sources`中的各来源
+    ///
+    /// 返回打开的凭据文件与导入Cookie时各来源缺失或已过期的登录Cookie，
+    /// 登录后可用`store_from`与`save`保存
+    pub fn load(
+        path: impl Into<PathBuf>,
+        passphrase: &Secret,
+        sources: &mut [&mut dyn VideoSource],
+    ) -> Result<(Self, Vec<(&'static str, CookieWarning)>)> {
+        let store = Self::open(path, passphrase)?;
+        let mut warnings = Vec::new();
+        for source in sources.iter_mut() {
+            let name = source.pretty_name();
+            for warning in store.load_into(&mut **source).unwrap_or_default() {
+                warnings.push((name, warning));
+            }
+        }
+        Ok((store, warnings))
+    }
+
+    /// 使用`default_path`的`load`
+    pub fn load_default(
+        passphrase: &Secret,
+        sources: &mut [&mut dyn VideoSource],
+    ) -> Result<(Self, Vec<(&'static str, CookieWarning)>)> {
+        let path = Self::default_path()
+            .ok_or_else(|| VideoSourceError::InvalidCredential("找不到用户配置目录".to_string()))?;
+        Self::load(path, passphrase, sources)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, source: &str) -> Option<&Credential> {
+        self.credentials.get(source)
+    }
+
+    pub fn set(&mut self, source: &str, credential: Credential) {
+        self.credentials.insert(source.to_string(), credential);
+    }
+
+    pub fn remove(&mut self, source: &str) -> Option<Credential> {
+        self.credentials.remove(source)
+    }
+
+    /// 保存了凭据的来源
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.credentials.keys().map(String::as_str)
+    }
+
+    /// 将保存的凭据设置到`source`，Cookie通过`VideoSource::import_cookies`导入
+    ///
+    /// 返回缺失或已过期的登录Cookie，没有保存时返回`None`
+    pub fn load_into<S: VideoSource + ?Sized>(&self, source: &mut S) -> Option<Vec<CookieWarning>> {
+        match self.get(source.pretty_name())? {
+            Credential::Token(token) => {
+                source.set_token(token.expose().to_string());
+                Some(Vec::new())
+            }
+            Credential::Cookies(cookies) => Some(source.import_cookies(cookies.clone())),
+        }
+    }
+
+    /// 记录`source`当前的Cookie，不使用Cookie的来源记录token，都没有时移除已保存的凭据。
+    /// 需调用`save`写入文件
+    pub fn store_from<S: VideoSource + ?Sized>(&mut self, source: &S) {
+        let credential = match (source.cookies(), source.token()) {
+            (Some(cookies), _) if !cookies.is_empty() => Some(Credential::Cookies(cookies.clone())),
+            (_, Some(token)) => Some(Credential::Token(token.into())),
+            _ => None,
+        };
+        match credential {
+            Some(credential) => self.set(source.pretty_name(), credential),
+            None => {
+                self.remove(source.pretty_name());
+            }
+        }
+    }
+
+    /// 加密写入文件，先写入临时文件再替换，Unix下文件只有所有者可读写
+    pub fn save(&self) -> Result<()> {
+        let credentials: BTreeMap<_, _> = self
+            .credentials
+            .iter()
+            .map(|(source, credential)| {
+                let credential = match credential {
+                    Credential::Token(token) => StoredCredential::Token(token.expose().to_string()),
+                    Credential::Cookies(cookies) => StoredCredential::Cookies {
+                        cookies: cookies.to_netscape(),
+                    },
+                };
+                (source.as_str(), credential)
+            })
+            .collect();
+        let plaintext = serde_json::to_vec(&credentials)
+            .map(Zeroizing::new)
+            .map_err(|error| VideoSourceError::InvalidCredential(error.to_string()))?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(self.key.as_slice()))
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| VideoSourceError::InvalidCredential("加密失败".to_string()))?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&self.salt)?;
+        file.write_all(&nonce)?;
+        file.write_all(&ciphertext)?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Debug for CredentialStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialStore")
+            .field("path", &self.path)
+            .field("credentials", &self.credentials)
+            .finish_non_exhaustive()
+    }
+}
+
+fn derive_key(passphrase: &Secret, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+    let mut key = Zeroizing::new([0; KEY_LEN]);
+    Argon2::default()
+        .hash_password_into(passphrase.expose().as_bytes(), salt, key.as_mut_slice())
+        .map_err(|error| VideoSourceError::InvalidCredential(error.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Credential, CredentialStore};
+    use crate::cookie::{CookieJar, CookieWarning};
+    use crate::error::VideoSourceError;
+    use crate::secret::Secret;
+    use crate::source::bilibili::{BilibiliLiveSource, BilibiliSource};
+    use crate::source::VideoSource;
+    use std::fs;
+
+    /// 2100年
+    const FUTURE: i64 = 4102444800;
+
+    #[test]
+    fn credential_store_test() {
+        let dir = std::env::temp_dir().join(format!("youngoor-credential-{}", std::process::id()));
+        let path = dir.join("credentials");
+        let passphrase = Secret::from("correct horse");
+
+        let mut source = BilibiliSource::new();
+        let cookies = CookieJar::from_netscape(&format!(
+            ".bilibili.com\tTRUE\t/\tTRUE\t{0}\tSESSDATA\tabc\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t{0}\tbili_jct\tdef\n\
+             www.bilibili.com\tFALSE\t/video\tFALSE\t0\tsession\t1\n",
+            FUTURE
+        ))
+        .unwrap();
+        assert!(source.import_cookies(cookies.clone()).is_empty());
+        // 日志中不显示Cookie
+        assert!(!format!("{:?}", source).contains("abc"));
+
+        let mut store = CredentialStore::open(&path, &passphrase).unwrap();
+        assert_eq!(store.sources().count(), 0);
+        store.store_from(&source);
+        // 只有token的凭据
+        store.set("token-only", Credential::Token("token".into()));
+        store.save().unwrap();
+        assert!(!format!("{:?}", store).contains("abc"));
+        let data = fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("SESSDATA"));
+
+        // 保存后再打开，Cookie的域名、路径与过期时间不变
+        let store = CredentialStore::open(&path, &passphrase).unwrap();
+        assert_eq!(
+            store.sources().collect::<Vec<_>>(),
+            vec!["bilibili", "token-only"]
+        );
+        assert_eq!(store.get("bilibili"), Some(&Credential::Cookies(cookies)));
+        assert_eq!(
+            store.get("token-only"),
+            Some(&Credential::Token("token".into()))
+        );
+        let mut loaded = BilibiliSource::new();
+        assert_eq!(store.load_into(&mut loaded), Some(vec![]));
+        assert_eq!(loaded.cookies(), source.cookies());
+        let sessdata = loaded.cookies().unwrap().get("SESSDATA").unwrap();
+        assert_eq!(sessdata.expires, Some(FUTURE));
+        assert!(sessdata.secure);
+        // 没有保存的来源
+        assert_eq!(store.load_into(&mut BilibiliLiveSource::new()), None);
+
+        // 启动时载入全部来源
+        let mut bilibili = BilibiliSource::new();
+        let mut live = BilibiliLiveSource::new();
+        let (store, warnings) =
+            CredentialStore::load(&path, &passphrase, &mut [&mut bilibili, &mut live]).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(bilibili.cookies(), source.cookies());
+        assert_eq!(live.token(), None);
+
+        // 移除登录Cookie后保存并载入，返回缺失的Cookie
+        let mut store = store;
+        bilibili.import_cookies(CookieJar::from_header("session=1", "bilibili.com"));
+        store.store_from(&bilibili);
+        store.save().unwrap();
+        let mut bilibili = BilibiliSource::new();
+        let (_, warnings) =
+            CredentialStore::load(&path, &passphrase, &mut [&mut bilibili]).unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                ("bilibili", CookieWarning::Missing("SESSDATA".to_string())),
+                ("bilibili", CookieWarning::Missing("bili_jct".to_string())),
+            ]
+        );
+
+        let result = CredentialStore::open(&path, &Secret::from("wrong"));
+        assert!(matches!(
+            result,
+            Err(VideoSourceError::InvalidCredential(_))
+        ));
+        fs::write(&path, b"not a credential file").unwrap();
+        let result = CredentialStore::open(&path, &passphrase);
+        assert!(matches!(
+            result,
+            Err(VideoSourceError::InvalidCredential(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+//! 文件下载，在镜像间自动切换
+
+use crate::error::VideoSourceError;
+use crate::source::Result;
+
+use reqwest::header::{HeaderMap, RANGE};
+use reqwest::{StatusCode, Url};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+
+/// 下载选项
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// 每个请求附加的请求头，如来源要求的Referer
+    pub headers: HeaderMap,
+    /// 连接或接收数据超过该时间时视为停滞，切换镜像
+    pub stall_timeout: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            stall_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// 下载器，同一会话中的下载应共用一个，以共享失败镜像的记录
+#[derive(Debug)]
+pub struct Downloader {
+    client: reqwest::Client,
+    options: DownloadOptions,
+    /// 无法连接或停滞的镜像（`协议://主机:端口`），本次会话中不再尝试
+    failed_mirrors: Mutex<HashSet<String>>,
+}
+
+/// 一次下载尝试的失败
+enum Failure {
+    /// 镜像无法连接或停滞，记录后切换到下一个镜像
+    Mirror(VideoSourceError),
+    /// 镜像上的该文件不可用，如地址过期返回403，只对当前文件切换镜像
+    Unavailable(VideoSourceError),
+    /// 与镜像无关的错误，如写入失败，直接返回
+    Fatal(VideoSourceError),
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Self::new(DownloadOptions::default())
+    }
+}
+
+impl Downloader {
+    pub fn new(options: DownloadOptions) -> Self {
+        Self {
+            client: reqwest::Client::default(),
+            options,
+            failed_mirrors: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 本次会话中无法连接或停滞的镜像
+    pub fn failed_mirrors(&self) -> Vec<String> {
+        let mut mirrors: Vec<_> = self
+            .failed_mirrors
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        mirrors.sort();
+        mirrors
+    }
+
+    /// 依次尝试`mirrors`中的地址，将同一文件下载到`output`，返回写入的字节数
+    ///
+    /// 出错时切换到下一个镜像，已写入的部分通过`Range`续传。连接错误或停滞时记录该镜像，
+    /// 之后的下载直接跳过；HTTP 403/404或服务器错误只对当前文件切换，不影响其他文件
+    pub async fn download<W>(&self, mirrors: &[Url], output: &mut W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut written = 0;
+        let mut last_error = None;
+        for url in mirrors {
+            let mirror = Self::mirror(url);
+            if self.failed_mirrors.lock().unwrap().contains(&mirror) {
+                continue;
+            }
+            match self.download_from(url, &mut written, output).await {
+                Ok(()) => {
+                    output.flush().await?;
+                    return Ok(written);
+                }
+                Err(Failure::Mirror(error)) => {
+                    self.failed_mirrors.lock().unwrap().insert(mirror);
+                    last_error = Some(error);
+                }
+                Err(Failure::Unavailable(error)) => last_error = Some(error),
+                Err(Failure::Fatal(error)) => return Err(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            let url = mirrors.first().map(Url::as_str).unwrap_or_default();
+            VideoSourceError::NoSuchResource(format!("没有可用的镜像: {}", url))
+        }))
+    }
+
+    /// 从`url`下载，`written`为已写入的字节数，下载过程中更新
+    async fn download_from<W>(
+        &self,
+        url: &Url,
+        written: &mut u64,
+        output: &mut W,
+    ) -> std::result::Result<(), Failure>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let stalled =
+            || Failure::Mirror(VideoSourceError::RequestError(format!("下载停滞: {}", url)));
+        let mut request = self
+            .client
+            .get(url.clone())
+            .headers(self.options.headers.clone());
+        if *written > 0 {
+            request = request.header(RANGE, format!("bytes={}-", written));
+        }
+        let mut response = match timeout(self.options.stall_timeout, request.send()).await {
+            Ok(response) => response.map_err(|error| Failure::Mirror(error.into()))?,
+            Err(_) => return Err(stalled()),
+        };
+        let status = response.status();
+        // 服务器不支持续传时跳过已写入的部分
+        let mut skip = match status {
+            StatusCode::PARTIAL_CONTENT => 0,
+            status if status.is_success() => *written,
+            status => {
+                let error = VideoSourceError::RequestError(format!("{}: {}", status, url));
+                return Err(if Self::is_unavailable(status) {
+                    Failure::Unavailable(error)
+                } else {
+                    Failure::Fatal(error)
+                });
+            }
+        };
+        loop {
+            let chunk = match timeout(self.options.stall_timeout, response.chunk()).await {
+                Ok(Ok(Some(chunk))) => chunk,
+                Ok(Ok(None)) => return Ok(()),
+                Ok(Err(error)) => return Err(Failure::Mirror(error.into())),
+                Err(_) => return Err(stalled()),
+            };
+            let skipped = skip.min(chunk.len() as u64);
+            skip -= skipped;
+            let chunk = chunk.slice(skipped as usize..);
+            output
+                .write_all(&chunk)
+                .await
+                .map_err(|error| Failure::Fatal(error.into()))?;
+            *written += chunk.len() as u64;
+        }
+    }
+
+    /// 该文件在镜像上不可用，可尝试其他镜像
+    fn is_unavailable(status: StatusCode) -> bool {
+        status == StatusCode::FORBIDDEN
+            || status == StatusCode::NOT_FOUND
+            || status.is_server_error()
+    }
+
+    fn mirror(url: &Url) -> String {
+        url.origin().ascii_serialization()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DownloadOptions, Downloader};
+    use crate::error::VideoSourceError;
+    use crate::source::mock::{MockResponse, MockServer};
+    use reqwest::Url;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// 支持`Range`续传
+    async fn file_server(requests: Arc<AtomicUsize>) -> MockServer {
+        MockServer::start(move |request| {
+            requests.fetch_add(1, Ordering::SeqCst);
+            match request
+                .header("range")
+                .and_then(|range| range.strip_prefix("bytes="))
+                .and_then(|range| range.strip_suffix('-'))
+            {
+                Some(start) => {
+                    let start: usize = start.parse().unwrap();
+                    MockResponse {
+                        status: 206,
+                        ..MockResponse::bytes("video/mp4", BODY[start..].to_vec())
+                    }
+                }
+                None => MockResponse::bytes("video/mp4", BODY.to_vec()),
+            }
+        })
+        .await
+    }
+
+    fn downloader() -> Downloader {
+        Downloader::new(DownloadOptions {
+            stall_timeout: Duration::from_millis(300),
+            ..DownloadOptions::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn download_failover_test() {
+        let forbidden_requests = Arc::new(AtomicUsize::new(0));
+        let counter = forbidden_requests.clone();
+        let forbidden = MockServer::start(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::status(403)
+        })
+        .await;
+        let file = file_server(Arc::new(AtomicUsize::new(0))).await;
+        // 无法连接的地址
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused =
+            Url::parse(&format!("http://{}/file", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+
+        let session = downloader();
+        let mirrors = [refused.clone(), forbidden.url("/file"), file.url("/file")];
+        let mut output = Vec::new();
+        let size = session.download(&mirrors, &mut output).await.unwrap();
+        assert_eq!((size, output.as_slice()), (BODY.len() as u64, BODY));
+        // 只记录无法连接的镜像
+        assert_eq!(
+            session.failed_mirrors(),
+            vec![refused.origin().ascii_serialization()]
+        );
+
+        // 无法连接的镜像不再尝试，返回403的镜像仍会尝试
+        let mut output = Vec::new();
+        session.download(&mirrors, &mut output).await.unwrap();
+        assert_eq!(output, BODY);
+        assert_eq!(forbidden_requests.load(Ordering::SeqCst), 2);
+
+        let result = session.download(&mirrors[..2], &mut Vec::new()).await;
+        assert!(matches!(result, Err(VideoSourceError::RequestError(_))));
+        let result = session.download(&mirrors[..1], &mut Vec::new()).await;
+        assert!(matches!(result, Err(VideoSourceError::NoSuchResource(_))));
+        // 其他状态码不切换镜像
+        let bad_request = MockServer::start(|_| MockResponse::status(400)).await;
+        let result = downloader()
+            .download(
+                &[bad_request.url("/file"), file.url("/file")],
+                &mut Vec::new(),
+            )
+            .await;
+        assert!(matches!(result, Err(VideoSourceError::RequestError(_))));
+    }
+
+    #[tokio::test]
+    async fn download_expired_test() {
+        // 过期的地址返回403，同一镜像上的其他文件仍可下载
+        let server = MockServer::start(|request| match request.url.path() {
+            "/expired" => MockResponse::status(403),
+            _ => MockResponse::bytes("video/mp4", BODY.to_vec()),
+        })
+        .await;
+        let session = downloader();
+        let result = session
+            .download(&[server.url("/expired")], &mut Vec::new())
+            .await;
+        assert!(matches!(result, Err(VideoSourceError::RequestError(_))));
+        assert!(session.failed_mirrors().is_empty());
+        let mut output = Vec::new();
+        session
+            .download(&[server.url("/file")], &mut output)
+            .await
+            .unwrap();
+        assert_eq!(output, BODY);
+    }
+
+    #[tokio::test]
+    async fn download_stall_test() {
+        let stalled =
+            MockServer::start(|_| MockResponse::bytes("video/mp4", BODY.to_vec()).with_stall(10))
+                .await;
+        let ranged_requests = Arc::new(AtomicUsize::new(0));
+        let ranged = file_server(ranged_requests.clone()).await;
+        let mut output = Vec::new();
+        let size = downloader()
+            .download(&[stalled.url("/file"), ranged.url("/file")], &mut output)
+            .await
+            .unwrap();
+        assert_eq!((size, output.as_slice()), (BODY.len() as u64, BODY));
+        assert_eq!(ranged_requests.load(Ordering::SeqCst), 1);
+
+        // 不支持续传时跳过已下载的部分
+        let plain = MockServer::start(|_| MockResponse::bytes("video/mp4", BODY.to_vec())).await;
+        let mut output = Vec::new();
+        downloader()
+            .download(&[stalled.url("/file"), plain.url("/file")], &mut output)
+            .await
+            .unwrap();
+        assert_eq!(output, BODY);
+    }
+}
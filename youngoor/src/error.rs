@@ -1,5 +1,5 @@
-use thiserror::Error;
 use reqwest::Url;
+use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum VideoSourceError {
@@ -15,4 +15,56 @@ pub enum VideoSourceError {
     NoSuchResource(String),
     #[error("无效的链接: {0}")]
     InvalidUrl(Url),
+    #[error("找不到链接: {0}")]
+    UrlNotFound(String),
+    #[error("无效的分P选择: {0}")]
+    InvalidPartSelection(String),
+    #[error("无效的格式选择: {0}")]
+    InvalidFormatSelection(String),
+    #[error("未购买: {0}")]
+    NotPurchased(String),
+    #[error("无效的条目: {0}")]
+    InvalidEntry(String),
+    #[error("无效的Cookie: {0}")]
+    InvalidCookie(String),
+    #[error("无效的凭据: {0}")]
+    InvalidCredential(String),
+    #[error("二维码已过期")]
+    QrCodeExpired,
+    #[error("IO错误: {0}")]
+    IoError(#[from] std::io::Error),
+    /// 列表中单个条目的错误，不影响其他条目
+    #[error("{title}（{id}）: {source}")]
+    Entry {
+        id: String,
+        title: String,
+        source: Box<VideoSourceError>,
+    },
+}
+
+impl VideoSourceError {
+    /// 将错误归属到条目，已归属条目的错误原样返回
+    pub fn entry(self, id: impl Into<String>, title: impl Into<String>) -> Self {
+        match self {
+            VideoSourceError::Entry { .. } => self,
+            error => VideoSourceError::Entry {
+                id: id.into(),
+                title: title.into(),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// 是否为单个条目的错误，列表中的其他条目仍会返回
+    pub fn is_entry(&self) -> bool {
+        matches!(self, VideoSourceError::Entry { .. })
+    }
+
+    /// 去掉条目信息后的错误
+    pub fn inner(&self) -> &VideoSourceError {
+        match self {
+            VideoSourceError::Entry { source, .. } => source.inner(),
+            error => error,
+        }
+    }
 }
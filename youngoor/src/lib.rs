@@ -1,4 +1,6 @@
+pub mod cookie;
+pub mod credential;
+pub mod download;
 pub mod error;
+pub mod secret;
 pub mod source;
-
-
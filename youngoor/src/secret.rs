@@ -0,0 +1,61 @@
+//! 不会被打印出来的敏感信息，如Cookie、口令
+
+use std::fmt::{Debug, Display, Formatter};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// 敏感字符串，`Debug`与`Display`只输出占位符，需要原文时调用`expose`，释放时清零
+#[derive(Clone, Default, PartialEq, Eq, Hash, Zeroize, ZeroizeOnDrop)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// 原文，只应在发送请求或加密保存时使用
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("***")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Secret;
+
+    #[test]
+    fn secret_redaction_test() {
+        let secret = Secret::from("SESSDATA=abc");
+        assert_eq!(format!("{:?}", secret), "Secret(***)");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some(Secret(***))");
+        assert_eq!(secret.to_string(), "***");
+        assert_eq!(secret.expose(), "SESSDATA=abc");
+    }
+}
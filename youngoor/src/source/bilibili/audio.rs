@@ -0,0 +1,248 @@
+//! 音频区：单曲（au）与歌单（am）
+
+use super::{BilibiliClient, BilibiliSource, EntryLocator};
+use crate::error::VideoSourceError;
+use crate::source::{EntryKey, PartSelection, Result, VideoEntry, VideoEntryStream, VideoMetadata};
+
+use reqwest::Url;
+use serde::Deserialize;
+use std::time::Duration;
+
+const REQUEST_SONG_INFO_URL: &str = "https://www.bilibili.com/audio/music-service-c/web/song/info";
+const REQUEST_SONG_URL: &str = "https://www.bilibili.com/audio/music-service-c/web/url";
+const REQUEST_MENU_SONGS_URL: &str =
+    "https://www.bilibili.com/audio/music-service-c/web/song/of-menu";
+/// 歌单每页数量
+const MENU_PAGE_SIZE: u32 = 100;
+/// 请求的音质：0为128K，1为192K，2为320K，3为无损。无权限时返回可用的最高音质
+const SONG_QUALITY: i32 = 2;
+
+impl BilibiliClient {
+    async fn request_song_info(&self, song_id: u64) -> Result<Song> {
+        let url = self.api_url(REQUEST_SONG_INFO_URL)?;
+        self.bilibili_http_get_not_null(
+            &url,
+            [("sid", song_id.to_string())].iter(),
+            self.has_cookie(),
+        )
+        .await
+    }
+
+    /// 请求音频下载地址
+    pub(super) async fn request_song_url(&self, song_id: u64) -> Result<Url> {
+        let url = self.api_url(REQUEST_SONG_URL)?;
+        let query_params = [
+            ("sid", song_id.to_string()),
+            ("privilege", "2".to_string()),
+            ("quality", SONG_QUALITY.to_string()),
+        ];
+        let result: SongUrl = self
+            .bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await?;
+        let url = result
+            .cdns
+            .into_iter()
+            .next()
+            .ok_or_else(|| VideoSourceError::NoSuchResource(format!("auid={}", song_id)))?;
+        BilibiliSource::parse_url(&url)
+    }
+
+    /// 请求歌单的一页歌曲，`page`从1开始
+    async fn request_menu_page(&self, menu_id: u64, page: u32) -> Result<MenuSongs> {
+        let url = self.api_url(REQUEST_MENU_SONGS_URL)?;
+        let query_params = [
+            ("sid", menu_id.to_string()),
+            ("pn", page.to_string()),
+            ("ps", MENU_PAGE_SIZE.to_string()),
+        ];
+        self.bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await
+    }
+}
+
+impl BilibiliSource {
+    /// 单曲，只有音频
+    pub(super) fn song(&self, song_id: u64) -> VideoEntryStream<'_> {
+        use async_stream::try_stream;
+
+        Box::pin(try_stream! {
+            let song = self.0.request_song_info(song_id).await?;
+            yield song.into_entry(None);
+        })
+    }
+
+    /// 逐页请求歌单，依次返回每首歌曲
+    ///
+    /// `parts`按歌单中的位置选择歌曲
+    pub(super) fn song_menu(&self, menu_id: u64, parts: PartSelection) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let mut page = 1;
+            let mut index = 0;
+            loop {
+                let songs = match self.0.request_menu_page(menu_id, page).await {
+                    Ok(songs) => songs,
+                    Err(error) => {
+                        yield Err(error);
+                        break;
+                    }
+                };
+                let last_page = songs.data.is_empty() || songs.cur_page >= songs.page_count;
+                for song in songs.data {
+                    index += 1;
+                    if parts.contains(index) {
+                        yield Ok(song.into_entry(Some(index)));
+                    }
+                }
+                if last_page {
+                    break;
+                }
+                page += 1;
+            }
+        })
+    }
+}
+
+/// 歌曲信息
+#[derive(Debug, Deserialize)]
+struct Song {
+    /// auid
+    pub id: u64,
+    pub title: String,
+    /// 封面
+    pub cover: String,
+    /// 歌词（LRC）地址，没有歌词时为空
+    pub lyric: String,
+    /// UP主
+    #[serde(default)]
+    pub uname: String,
+    /// 时长，秒
+    #[serde(default)]
+    pub duration: u64,
+    /// 发布时间（Unix时间戳，秒）
+    #[serde(default)]
+    pub passtime: i64,
+    /// 简介
+    #[serde(default)]
+    pub intro: String,
+}
+
+impl Song {
+    /// `index`为歌曲在歌单中的位置
+    fn into_entry(self, index: Option<u32>) -> VideoEntry {
+        VideoEntry {
+            pic: Url::parse(&self.cover).ok(),
+            lyric: Url::parse(&self.lyric).ok(),
+            key: EntryKey::new(EntryLocator::Song { song_id: self.id }),
+            metadata: VideoMetadata {
+                id: Some(format!("au{}", self.id)),
+                duration: Some(Duration::from_secs(self.duration)),
+                uploader: Some(self.uname).filter(|uname| !uname.is_empty()),
+                published: Some(self.passtime).filter(|&time| time > 0),
+                description: Some(self.intro).filter(|intro| !intro.is_empty()),
+                episode: index,
+                ..VideoMetadata::default()
+            },
+            title: self.title,
+        }
+    }
+}
+
+/// 歌曲下载地址
+#[derive(Debug, Deserialize)]
+struct SongUrl {
+    pub cdns: Vec<String>,
+}
+
+/// 歌单中的歌曲
+#[derive(Debug, Deserialize)]
+struct MenuSongs {
+    #[serde(rename(deserialize = "curPage"))]
+    pub cur_page: u32,
+    #[serde(rename(deserialize = "pageCount"))]
+    pub page_count: u32,
+    pub data: Vec<Song>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{
+        test::{fixture, fixture_routes, mock_api},
+        DimensionCode,
+    };
+    use crate::source::mock::{MockResponse, MockRoutes};
+    use crate::source::{PartSelection, StreamPreference, VideoSource, VideoType};
+    use futures::StreamExt;
+    use reqwest::Url;
+    use std::time::Duration;
+
+    fn audio_routes() -> MockRoutes {
+        fixture_routes(&[("/audio/music-service-c/web/song/info", "audio_song.json")])
+            .route("/audio/music-service-c/web/url", |request| {
+                match request.query("sid").as_deref() {
+                    Some("404") => MockResponse::json(r#"{"code":72000000,"msg":"歌曲不存在"}"#),
+                    _ => MockResponse::json(fixture("audio_url.json")),
+                }
+            })
+            .route(
+                "/audio/music-service-c/web/song/of-menu",
+                |request| match request.query("pn").as_deref() {
+                    Some("1") => MockResponse::json(fixture("audio_menu.json")),
+                    _ => MockResponse::status(404),
+                },
+            )
+    }
+
+    #[tokio::test]
+    async fn song_test() {
+        let (_server, source) = mock_api(audio_routes()).await;
+        let songs: Vec<_> = source
+            .video_list(
+                &Url::parse("https://www.bilibili.com/audio/au1986237").unwrap(),
+                VideoType::MP4,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(songs.len(), 1);
+        let song = songs[0].as_ref().unwrap();
+        assert_eq!(song.title, "Zapomni");
+        assert!(song.video.is_empty());
+        assert_eq!(song.audio.len(), 1);
+        assert!(song.audio[0].path().ends_with(".m4a"));
+        assert!(song.pic.is_some());
+        assert!(song.lyric.as_ref().unwrap().path().ends_with(".lrc"));
+        assert_eq!(song.metadata.id.as_deref(), Some("au1986237"));
+        assert_eq!(song.metadata.uploader.as_deref(), Some("冰封.虾子"));
+        assert_eq!(song.metadata.duration, Some(Duration::from_secs(240)));
+        assert_eq!(song.metadata.published, Some(1577808000));
+        assert_eq!(song.metadata.episode, None);
+    }
+
+    #[tokio::test]
+    async fn song_menu_test() {
+        let (_server, source) = mock_api(audio_routes()).await;
+        let songs: Vec<_> = source
+            .video_list(
+                &Url::parse("https://www.bilibili.com/audio/am10624").unwrap(),
+                VideoType::MP4,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(songs.len(), 3);
+        assert_eq!(songs[0].as_ref().unwrap().title, "Zapomni");
+        assert!(songs[0].as_ref().unwrap().lyric.is_some());
+        assert!(songs[1].is_err());
+        assert_eq!(songs[2].as_ref().unwrap().title, "Lubov");
+        assert_eq!(songs[2].as_ref().unwrap().metadata.episode, Some(3));
+        assert!(songs[2].as_ref().unwrap().lyric.is_none());
+    }
+}
@@ -0,0 +1,93 @@
+//! AV号与BV号互相转换（离线算法）
+
+use std::convert::TryInto;
+
+const XOR_CODE: u64 = 23442827791579;
+const MASK_CODE: u64 = 2251799813685247;
+/// 可表示的最大AV号（不含）
+pub const MAX_AID: u64 = 1 << 51;
+const TABLE: &[u8; 58] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+const BVID_LEN: usize = 12;
+
+/// AV号转BV号，`aid`超出范围时返回`None`
+pub fn av_to_bv(aid: u64) -> Option<String> {
+    if aid == 0 || aid >= MAX_AID {
+        return None;
+    }
+    let mut bytes = *b"BV1000000000";
+    let mut tmp = (MAX_AID | aid) ^ XOR_CODE;
+    let mut index = BVID_LEN - 1;
+    while tmp > 0 {
+        bytes[index] = TABLE[(tmp % 58) as usize];
+        tmp /= 58;
+        index -= 1;
+    }
+    bytes.swap(3, 9);
+    bytes.swap(4, 7);
+    Some(bytes.iter().map(|&byte| byte as char).collect())
+}
+
+/// BV号转AV号，`bvid`格式错误时返回`None`
+pub fn bv_to_av(bvid: &str) -> Option<u64> {
+    let bytes = bvid.as_bytes();
+    if bytes.len() != BVID_LEN || !bytes[..2].eq_ignore_ascii_case(b"BV") {
+        return None;
+    }
+    let mut bytes: [u8; BVID_LEN] = bytes.try_into().ok()?;
+    bytes.swap(3, 9);
+    bytes.swap(4, 7);
+    let mut tmp: u64 = 0;
+    for byte in &bytes[3..] {
+        let value = TABLE.iter().position(|code| code == byte)? as u64;
+        tmp = tmp.checked_mul(58)?.checked_add(value)?;
+    }
+    let aid = (tmp & MASK_CODE) ^ XOR_CODE;
+    if aid == 0 || aid >= MAX_AID {
+        None
+    } else {
+        Some(aid)
+    }
+}
+
+/// 解析`av170001`/`AV170001`形式的AV号
+pub fn parse_aid(avid: &str) -> Option<u64> {
+    let prefix = avid.get(..2)?;
+    if !prefix.eq_ignore_ascii_case("av") {
+        return None;
+    }
+    avid[2..].parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{av_to_bv, bv_to_av, parse_aid, MAX_AID};
+
+    #[test]
+    fn av_to_bv_test() {
+        assert_eq!(av_to_bv(170001).unwrap(), "BV17x411w7KC");
+        assert_eq!(av_to_bv(1).unwrap(), "BV1xx411c7mQ");
+        assert_eq!(av_to_bv(111298867365120).unwrap(), "BV1L9Uoa9EUx");
+        assert_eq!(av_to_bv(0), None);
+        assert_eq!(av_to_bv(MAX_AID), None);
+    }
+
+    #[test]
+    fn bv_to_av_test() {
+        assert_eq!(bv_to_av("BV17x411w7KC"), Some(170001));
+        assert_eq!(bv_to_av("BV1L9Uoa9EUx"), Some(111298867365120));
+        assert_eq!(bv_to_av("BV1ex411J7GE"), Some(13502509));
+        assert_eq!(bv_to_av("BVXXXXXX"), None);
+        assert_eq!(bv_to_av("BV1ex411J7G0"), None);
+        for aid in [2, 99999999, 1054803170, MAX_AID - 1] {
+            assert_eq!(bv_to_av(&av_to_bv(aid).unwrap()), Some(aid));
+        }
+    }
+
+    #[test]
+    fn parse_aid_test() {
+        assert_eq!(parse_aid("av170001"), Some(170001));
+        assert_eq!(parse_aid("AV170001"), Some(170001));
+        assert_eq!(parse_aid("av"), None);
+        assert_eq!(parse_aid("BV17x411w7KC"), None);
+    }
+}
@@ -0,0 +1,384 @@
+//! 合集（UGC season）与系列
+
+use super::{BilibiliClient, BilibiliSource};
+use crate::error::VideoSourceError;
+use crate::source::{PartSelection, Result, VideoEntryStream, VideoMetadata};
+
+use reqwest::Url;
+use serde::Deserialize;
+
+const REQUEST_COLLECTION_ARCHIVES_URL: &str =
+    "https://api.bilibili.com/x/polymer/web-space/seasons_archives_list";
+const REQUEST_SERIES_ARCHIVES_URL: &str = "https://api.bilibili.com/x/series/archives";
+/// 系列每页数量
+const SERIES_PAGE_SIZE: u32 = 30;
+
+impl BilibiliClient {
+    /// 请求合集的分区与分集
+    ///
+    /// 合集列表接口不含分区信息，先取合集中任一视频，再从视频详情中获取完整合集
+    async fn request_collection(&self, mid: u64, season_id: u64) -> Result<UgcSeason> {
+        let url = self.api_url(REQUEST_COLLECTION_ARCHIVES_URL)?;
+        let query_params = [
+            ("mid", mid.to_string()),
+            ("season_id", season_id.to_string()),
+            ("page_num", "1".to_string()),
+            ("page_size", "1".to_string()),
+        ];
+        let archives: ArchiveList = self
+            .bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await?;
+        let not_found = || VideoSourceError::NoSuchResource(format!("合集: sid={}", season_id));
+        let archive = archives.archives.into_iter().next().ok_or_else(not_found)?;
+        self.request_video_info(&archive.bvid)
+            .await?
+            .view
+            .ugc_season
+            .filter(|season| season.id == season_id)
+            .ok_or_else(not_found)
+    }
+
+    /// 请求系列的一页视频，`page`从1开始
+    async fn request_series_page(
+        &self,
+        mid: u64,
+        series_id: u64,
+        page: u32,
+    ) -> Result<ArchiveList> {
+        let url = self.api_url(REQUEST_SERIES_ARCHIVES_URL)?;
+        let query_params = [
+            ("mid", mid.to_string()),
+            ("series_id", series_id.to_string()),
+            ("only_normal", "true".to_string()),
+            ("sort", "asc".to_string()),
+            ("pn", page.to_string()),
+            ("ps", SERIES_PAGE_SIZE.to_string()),
+        ];
+        self.bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await
+    }
+}
+
+impl BilibiliSource {
+    /// 按合集的分区与分集顺序依次返回每个视频，获取音视频流时再展开为各分P
+    ///
+    /// 只有一个分区时标题为`序号 标题`，否则为`分区标题 序号 标题`，序号在分区内从1开始。
+    /// `parts`按合集中的总顺序选择分集
+    pub(super) fn collection_list(
+        &self,
+        mid: u64,
+        season_id: u64,
+        parts: PartSelection,
+    ) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let season = match self.0.request_collection(mid, season_id).await {
+                Ok(season) => season,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            let series_url = season.url();
+            let multi_section = season.sections.len() > 1;
+            let mut index = 0;
+            for section in &season.sections {
+                for (episode, number) in section.episodes.iter().zip(1..) {
+                    index += 1;
+                    if !parts.contains(index) {
+                        continue;
+                    }
+                    let title = if multi_section {
+                        format!("{} {} {}", section.title, number, episode.title)
+                    } else {
+                        format!("{} {}", number, episode.title)
+                    };
+                    yield Ok(Self::list_video(
+                        episode.bvid.clone(),
+                        title,
+                        Url::parse(&episode.arc.pic).ok(),
+                        VideoMetadata {
+                            series: Some(season.title.clone()),
+                            series_url: series_url.clone(),
+                            episode: Some(index),
+                            ..VideoMetadata::default()
+                        },
+                    ));
+                }
+            }
+        })
+    }
+
+    /// 逐页请求系列，按发布时间从旧到新依次返回每个视频，标题前带有序号，获取音视频流时再展开为各分P
+    ///
+    /// `parts`按系列中的位置选择视频
+    pub(super) fn series_list(
+        &self,
+        mid: u64,
+        series_id: u64,
+        parts: PartSelection,
+    ) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let mut page = 1;
+            let mut index = 0;
+            loop {
+                let list = match self.0.request_series_page(mid, series_id, page).await {
+                    Ok(list) => list,
+                    Err(error) => {
+                        yield Err(error);
+                        break;
+                    }
+                };
+                let last_page = list.archives.is_empty()
+                    || list
+                        .page
+                        .is_none_or(|page| page.num * page.size >= page.total);
+                for archive in list.archives {
+                    index += 1;
+                    if !parts.contains(index) {
+                        continue;
+                    }
+                    yield Ok(Self::list_video(
+                        archive.bvid,
+                        format!("{} {}", index, archive.title),
+                        Url::parse(&archive.pic).ok(),
+                        VideoMetadata {
+                            episode: Some(index),
+                            ..VideoMetadata::default()
+                        },
+                    ));
+                }
+                if last_page {
+                    break;
+                }
+                page += 1;
+            }
+        })
+    }
+}
+
+/// 合集
+#[derive(Debug, Deserialize)]
+pub(super) struct UgcSeason {
+    pub id: u64,
+    pub title: String,
+    /// UP主
+    pub mid: u64,
+    pub sections: Vec<UgcSection>,
+}
+
+/// 合集分区
+#[derive(Debug, Deserialize)]
+pub(super) struct UgcSection {
+    pub title: String,
+    pub episodes: Vec<UgcEpisode>,
+}
+
+impl UgcSeason {
+    /// 合集在UP主空间中的链接
+    pub(super) fn url(&self) -> Option<Url> {
+        Url::parse(&format!(
+            "https://space.bilibili.com/{}/channel/collectiondetail?sid={}",
+            self.mid, self.id
+        ))
+        .ok()
+    }
+}
+
+/// 合集分集
+#[derive(Debug, Deserialize)]
+pub(super) struct UgcEpisode {
+    pub bvid: String,
+    pub title: String,
+    pub arc: UgcArchive,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct UgcArchive {
+    /// 封面
+    pub pic: String,
+}
+
+/// 合集或系列中的视频列表
+#[derive(Debug, Deserialize)]
+struct ArchiveList {
+    pub archives: Vec<Archive>,
+    pub page: Option<ArchivePage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Archive {
+    pub bvid: String,
+    pub title: String,
+    /// 封面
+    pub pic: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchivePage {
+    #[serde(alias = "page_num")]
+    pub num: u32,
+    #[serde(alias = "page_size")]
+    pub size: u32,
+    pub total: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{
+        test::{fixture, fixture_routes, list_videos, mock_api},
+        DimensionCode,
+    };
+    use crate::source::mock::{MockResponse, MockRoutes};
+    use crate::source::{StreamPreference, VideoSource, VideoType};
+    use futures::StreamExt;
+    use reqwest::Url;
+    use std::time::Duration;
+
+    fn collection_routes() -> MockRoutes {
+        fixture_routes(&[("/x/player/playurl", "playurl_durl.json")])
+            .route("/x/polymer/web-space/seasons_archives_list", |request| {
+                assert_eq!(request.query("season_id").as_deref(), Some("1234"));
+                MockResponse::json(fixture("collection_archives.json"))
+            })
+            .route("/x/web-interface/view/detail", |request| {
+                match request.query("bvid").as_deref() {
+                    Some("BV1ex411J7GE") => MockResponse::json(fixture("view_detail_season.json")),
+                    _ => MockResponse::json(fixture("view_detail_single.json")),
+                }
+            })
+            .route("/x/series/archives", |request| {
+                match request.query("pn").as_deref() {
+                    Some("1") => MockResponse::json(fixture("series_page1.json")),
+                    Some("2") => MockResponse::json(fixture("series_page2.json")),
+                    _ => MockResponse::status(404),
+                }
+            })
+    }
+
+    async fn list_titles(source: &dyn VideoSource, url: &str, parts: &str) -> Vec<String> {
+        source
+            .video_list(
+                &Url::parse(url).unwrap(),
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &parts.parse().unwrap(),
+            )
+            .unwrap()
+            .map(|video| video.unwrap().title)
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn collection_list_test() {
+        let (_server, source) = mock_api(collection_routes()).await;
+        let titles = list_titles(
+            &source,
+            "https://space.bilibili.com/2/channel/collectiondetail?sid=1234",
+            "",
+        )
+        .await;
+        // 多P视频返回全部分P
+        assert_eq!(
+            titles,
+            vec![
+                "第一章 1 入门 00. 宣传短片",
+                "第一章 1 入门 01. 火柴人与动画师",
+                "第一章 1 入门 02. 火柴人与动画师 II",
+                "第一章 2 环境搭建",
+                "第二章 1 进阶",
+            ]
+        );
+        let titles = list_titles(
+            &source,
+            "https://space.bilibili.com/2/lists/1234?type=season",
+            "3",
+        )
+        .await;
+        assert_eq!(titles, vec!["第二章 1 进阶"]);
+    }
+
+    #[tokio::test]
+    async fn collection_metadata_test() {
+        let (_server, source) = mock_api(collection_routes()).await;
+        let videos = list_videos(
+            &source,
+            "https://space.bilibili.com/2/channel/collectiondetail?sid=1234",
+            "2-",
+        )
+        .await;
+        let collection_url =
+            Url::parse("https://space.bilibili.com/2/channel/collectiondetail?sid=1234").unwrap();
+        let metadata = &videos[0].metadata;
+        assert_eq!(metadata.id.as_deref(), Some("BV17x411w7KC"));
+        assert_eq!(metadata.series.as_deref(), Some("教程合集"));
+        assert_eq!(metadata.series_url.as_ref(), Some(&collection_url));
+        assert_eq!(metadata.episode, Some(2));
+        assert_eq!(metadata.part, Some(1));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(2088)));
+        assert_eq!(videos[1].metadata.episode, Some(3));
+
+        let videos = list_videos(&source, "https://space.bilibili.com/2/lists/1234", "1").await;
+        assert_eq!(videos.len(), 3);
+        assert!(videos
+            .iter()
+            .all(|video| video.metadata.episode == Some(1) && video.metadata.part.is_some()));
+
+        let videos = list_videos(
+            &source,
+            "https://space.bilibili.com/2/channel/seriesdetail?sid=5678",
+            "2",
+        )
+        .await;
+        assert_eq!(videos[0].metadata.episode, Some(2));
+        assert_eq!(videos[0].metadata.uploader.as_deref(), Some("冰封.虾子"));
+    }
+
+    #[tokio::test]
+    async fn series_list_test() {
+        let (_server, source) = mock_api(collection_routes()).await;
+        let titles = list_titles(
+            &source,
+            "https://space.bilibili.com/2/channel/seriesdetail?sid=5678",
+            "",
+        )
+        .await;
+        assert_eq!(
+            titles,
+            vec![
+                "1 系列一",
+                "2 系列二",
+                "3 系列三 00. 宣传短片",
+                "3 系列三 01. 火柴人与动画师",
+                "3 系列三 02. 火柴人与动画师 II",
+            ]
+        );
+        let titles = list_titles(
+            &source,
+            "https://space.bilibili.com/2/lists/5678?type=series",
+            "2-",
+        )
+        .await;
+        assert_eq!(titles[..2], ["2 系列二", "3 系列三 00. 宣传短片"]);
+    }
+
+    #[tokio::test]
+    async fn collection_url_test() {
+        let (_server, source) = mock_api(collection_routes()).await;
+        let videos = list_videos(&source, "https://www.bilibili.com/video/BV1ex411J7GE", "").await;
+        assert_eq!(
+            videos[0].metadata.series_url,
+            Some(
+                Url::parse("https://space.bilibili.com/2/channel/collectiondetail?sid=1234")
+                    .unwrap()
+            )
+        );
+        let videos = list_videos(&source, "https://www.bilibili.com/video/BV17x411w7KC", "").await;
+        assert_eq!(videos[0].metadata.series_url, None);
+    }
+}
@@ -0,0 +1,308 @@
+//! 课程（课堂），全部接口都需要登录
+
+use super::{
+    BilibiliClient, BilibiliSource, DimensionCode, EntryLocator, PlayUrl, VideoTypeCode,
+    VideoUrlInfo,
+};
+use crate::error::VideoSourceError;
+use crate::source::{
+    EntryKey, PartSelection, Result, StreamPreference, VideoEntry, VideoEntryStream, VideoMetadata,
+};
+
+use reqwest::Url;
+use serde::Deserialize;
+use std::time::Duration;
+
+const REQUEST_COURSE_INFO_URL: &str = "https://api.bilibili.com/pugv/view/web/season";
+const REQUEST_COURSE_VIDEO_URL: &str = "https://api.bilibili.com/pugv/player/web/playurl";
+/// 分集状态：可观看（已购买或可试看）
+const EPISODE_STATUS_PLAYABLE: i32 = 1;
+
+impl BilibiliClient {
+    /// 请求课程的全部分集，`query_param`为`season_id`或`ep_id`
+    async fn request_course_info(&self, query_param: (&str, String)) -> Result<CourseInfo> {
+        let url = self.api_url(REQUEST_COURSE_INFO_URL)?;
+        self.bilibili_http_get_not_null(&url, [query_param].iter(), true)
+            .await
+    }
+
+    /// `episode`为`(aid, cid, ep_id)`，`title`用于未购买时的错误信息
+    pub(super) async fn request_course_video_url(
+        &self,
+        episode: (u64, u64, u64),
+        title: &str,
+        video_type: VideoTypeCode,
+        dimension: DimensionCode,
+        preference: &StreamPreference,
+    ) -> Result<PlayUrl> {
+        let (aid, cid, episode_id) = episode;
+        let url = self.api_url(REQUEST_COURSE_VIDEO_URL)?;
+        let query_params = [
+            ("avid", aid.to_string()),
+            ("cid", cid.to_string()),
+            ("ep_id", episode_id.to_string()),
+            ("qn", i32::from(dimension).to_string()),
+            ("fnval", video_type.fnval(dimension, preference).to_string()),
+            ("fnver", "0".to_string()),
+            ("fourk", "1".to_string()),
+        ];
+        let result: CourseVideoUrlInfo = self
+            .bilibili_http_get_not_null(&url, query_params.iter(), true)
+            .await?;
+        if result.is_preview != 0 {
+            return Err(not_purchased(episode_id, title));
+        }
+        result
+            .info
+            .into_urls(dimension, preference, &format!("ep_id={}", episode_id))
+    }
+}
+
+impl BilibiliSource {
+    /// 按课程顺序依次返回分集，标题为`序号 标题`
+    ///
+    /// 指定`episode_id`时只返回该分集，否则`parts`按课程中的位置选择分集。
+    /// 未购买的分集以`Err(Entry)`返回，不会中断后续分集
+    pub(super) fn course_list(
+        &self,
+        query_param: (&'static str, u64),
+        episode_id: Option<u64>,
+        parts: PartSelection,
+    ) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let (key, id) = query_param;
+            let course = match self.0.request_course_info((key, id.to_string())).await {
+                Ok(course) => course,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            let episodes: Vec<_> = match episode_id {
+                Some(episode_id) => {
+                    let episode = course.episodes.iter().find(|episode| episode.id == episode_id);
+                    if episode.is_none() {
+                        yield Err(VideoSourceError::NoSuchResource(format!("ep_id={}", episode_id)));
+                    }
+                    episode.into_iter().collect()
+                }
+                None => course
+                    .episodes
+                    .iter()
+                    .zip(1..)
+                    .filter(|(_, index)| parts.contains(*index))
+                    .map(|(episode, _)| episode)
+                    .collect(),
+            };
+            for episode in episodes {
+                let title = format!("{} {}", episode.index, episode.title);
+                if episode.status != EPISODE_STATUS_PLAYABLE {
+                    yield Err(episode.not_purchased().entry(format!("ep{}", episode.id), title));
+                    continue;
+                }
+                yield Ok(VideoEntry {
+                    pic: Url::parse(&episode.cover).ok(),
+                    title,
+                    lyric: None,
+                    metadata: course.metadata(episode),
+                    key: EntryKey::new(EntryLocator::Course {
+                        aid: episode.aid,
+                        cid: episode.cid,
+                        episode_id: episode.id,
+                    }),
+                });
+            }
+        })
+    }
+}
+
+/// 课程
+#[derive(Debug, Deserialize)]
+struct CourseInfo {
+    pub title: String,
+    /// 简介
+    #[serde(default)]
+    pub subtitle: String,
+    pub episodes: Vec<CourseEpisode>,
+}
+
+impl CourseInfo {
+    fn metadata(&self, episode: &CourseEpisode) -> VideoMetadata {
+        VideoMetadata {
+            id: Some(format!("ep{}", episode.id)),
+            duration: Some(Duration::from_secs(episode.duration)),
+            published: Some(episode.release_date).filter(|&time| time > 0),
+            description: Some(self.subtitle.clone()).filter(|subtitle| !subtitle.is_empty()),
+            series: Some(self.title.clone()),
+            episode: Some(episode.index),
+            ..VideoMetadata::default()
+        }
+    }
+}
+
+/// 课程分集
+#[derive(Debug, Deserialize)]
+struct CourseEpisode {
+    /// AV号
+    pub aid: u64,
+    pub cid: u64,
+    /// epid
+    pub id: u64,
+    /// 课程内的序号
+    pub index: u32,
+    pub title: String,
+    /// 封面
+    pub cover: String,
+    /// 1: 可观看，2: 未购买
+    pub status: i32,
+    /// 时长，秒
+    #[serde(default)]
+    pub duration: u64,
+    /// 发布时间（Unix时间戳，秒）
+    #[serde(default)]
+    pub release_date: i64,
+}
+
+impl CourseEpisode {
+    fn not_purchased(&self) -> VideoSourceError {
+        not_purchased(self.id, &self.title)
+    }
+}
+
+fn not_purchased(episode_id: u64, title: &str) -> VideoSourceError {
+    VideoSourceError::NotPurchased(format!("ep_id={},title={}", episode_id, title))
+}
+
+/// 课程下载地址
+#[derive(Debug, Deserialize)]
+struct CourseVideoUrlInfo {
+    #[serde(flatten)]
+    pub info: VideoUrlInfo,
+    /// 未购买时只能获取试看片段
+    #[serde(default)]
+    pub is_preview: i32,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{
+        test::{fixture, mock_api},
+        DimensionCode,
+    };
+    use crate::error::VideoSourceError;
+    use crate::source::mock::{MockRequest, MockResponse, MockRoutes};
+    use crate::source::{StreamPreference, VideoInfo, VideoSource, VideoType};
+    use futures::StreamExt;
+    use reqwest::Url;
+    use std::time::Duration;
+
+    /// 未登录时返回-101
+    fn need_login<F>(handler: F) -> impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        move |request| {
+            if request.header("cookie") == Some("SESSDATA=test") {
+                handler(request)
+            } else {
+                MockResponse::json(r#"{"code":-101,"message":"账号未登录"}"#)
+            }
+        }
+    }
+
+    fn course_routes() -> MockRoutes {
+        MockRoutes::new()
+            .route(
+                "/pugv/view/web/season",
+                need_login(|request| {
+                    assert!(
+                        request.query("season_id").is_some() || request.query("ep_id").is_some()
+                    );
+                    MockResponse::json(fixture("course_season.json"))
+                }),
+            )
+            .route(
+                "/pugv/player/web/playurl",
+                need_login(|request| match request.query("ep_id").as_deref() {
+                    Some("8004") => MockResponse::json(
+                        fixture("playurl_durl.json")
+                            .replace(r#""quality""#, r#""is_preview": 1, "quality""#),
+                    ),
+                    _ => MockResponse::json(fixture("playurl_durl.json")),
+                }),
+            )
+    }
+
+    async fn list(
+        source: &dyn VideoSource,
+        url: &str,
+        parts: &str,
+    ) -> Vec<Result<VideoInfo, VideoSourceError>> {
+        source
+            .video_list(
+                &Url::parse(url).unwrap(),
+                VideoType::Flv,
+                &DimensionCode::P1080.quality(),
+                &StreamPreference::default(),
+                &parts.parse().unwrap(),
+            )
+            .unwrap()
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn course_list_test() {
+        let (_server, mut source) = mock_api(course_routes()).await;
+        let url = "https://www.bilibili.com/cheese/play/ss360";
+
+        let videos = list(&source, url, "").await;
+        assert_eq!(videos.len(), 1);
+        assert!(matches!(videos[0], Err(VideoSourceError::NeedLogin)));
+
+        source.set_token("SESSDATA=test".to_string());
+        let videos = list(&source, url, "").await;
+        assert_eq!(videos.len(), 4);
+        assert_eq!(videos[0].as_ref().unwrap().title, "1 课程介绍");
+        assert_eq!(videos[1].as_ref().unwrap().title, "2 环境搭建");
+        assert!(videos[1].as_ref().unwrap().pic.is_some());
+        // 未购买
+        let error = videos[2].as_ref().unwrap_err();
+        assert!(matches!(error.inner(), VideoSourceError::NotPurchased(_)));
+        assert_eq!(
+            error.to_string(),
+            "3 所有权（ep8003）: 未购买: ep_id=8003,title=所有权"
+        );
+        // 分集可试看，但下载地址只有试看片段
+        let error = videos[3].as_ref().unwrap_err();
+        assert!(matches!(error.inner(), VideoSourceError::NotPurchased(_)));
+
+        let videos = list(&source, url, "2").await;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].as_ref().unwrap().title, "2 环境搭建");
+
+        let videos = list(&source, "https://www.bilibili.com/cheese/play/ep8002", "").await;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].as_ref().unwrap().title, "2 环境搭建");
+        let metadata = &videos[0].as_ref().unwrap().metadata;
+        assert_eq!(metadata.id.as_deref(), Some("ep8002"));
+        assert_eq!(metadata.series.as_deref(), Some("从零开始的Rust编程"));
+        assert_eq!(metadata.episode, Some(2));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(1200)));
+        assert_eq!(metadata.published, Some(1700086400));
+
+        let videos = list(&source, "https://www.bilibili.com/cheese/play/ep8003", "").await;
+        assert!(matches!(
+            &videos[..],
+            [Err(error)] if matches!(error.inner(), VideoSourceError::NotPurchased(_))
+        ));
+
+        let videos = list(&source, "https://www.bilibili.com/cheese/play/ep9999", "").await;
+        assert!(matches!(
+            videos[..],
+            [Err(VideoSourceError::NoSuchResource(_))]
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+//! 收藏夹
+
+use super::{BilibiliClient, BilibiliSource};
+use crate::error::VideoSourceError;
+use crate::source::{PartSelection, Result, VideoEntryStream, VideoMetadata};
+
+use reqwest::Url;
+use serde::Deserialize;
+
+const REQUEST_FAVORITE_LIST_URL: &str = "https://api.bilibili.com/x/v3/fav/resource/list";
+/// 每页数量，接口上限为20
+const FAVORITE_PAGE_SIZE: u32 = 20;
+/// 收藏内容类型：视频
+const MEDIA_TYPE_VIDEO: i32 = 2;
+
+impl BilibiliClient {
+    /// 请求收藏夹的一页内容，`page`从1开始
+    async fn request_favorite_page(&self, media_id: u64, page: u32) -> Result<FavoriteList> {
+        let url = self.api_url(REQUEST_FAVORITE_LIST_URL)?;
+        let query_params = [
+            ("media_id", media_id.to_string()),
+            ("pn", page.to_string()),
+            ("ps", FAVORITE_PAGE_SIZE.to_string()),
+            ("platform", "web".to_string()),
+        ];
+        self.bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await
+    }
+}
+
+impl BilibiliSource {
+    /// 逐页请求收藏夹，依次返回每个视频，获取音视频流时再展开为各分P
+    ///
+    /// `parts`按收藏夹中的位置选择视频。失效的视频以`Err(Entry)`返回，不会中断后续视频
+    pub(super) fn favorite_list(
+        &self,
+        media_id: u64,
+        parts: PartSelection,
+    ) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let mut page = 1;
+            let mut index = 0;
+            loop {
+                let list = match self.0.request_favorite_page(media_id, page).await {
+                    Ok(list) => list,
+                    Err(error) => {
+                        yield Err(error);
+                        break;
+                    }
+                };
+                let medias = list.medias.unwrap_or_default();
+                // 部分收藏夹在最后一页之后仍返回`has_more`
+                if medias.is_empty() {
+                    break;
+                }
+                for media in medias {
+                    index += 1;
+                    if !parts.contains(index) {
+                        continue;
+                    }
+                    if let Err(error) = media.check() {
+                        yield Err(error.entry(media.bvid, media.title));
+                        continue;
+                    }
+                    yield Ok(Self::list_video(
+                        media.bvid,
+                        media.title,
+                        Url::parse(&media.cover).ok(),
+                        VideoMetadata::default(),
+                    ));
+                }
+                if !list.has_more || parts.last().is_some_and(|last| index >= last) {
+                    break;
+                }
+                page += 1;
+            }
+        })
+    }
+}
+
+/// 收藏夹内容
+#[derive(Debug, Deserialize)]
+struct FavoriteList {
+    /// 收藏夹为空时为`null`
+    pub medias: Option<Vec<FavoriteMedia>>,
+    pub has_more: bool,
+}
+
+/// 收藏的视频
+#[derive(Debug, Deserialize)]
+struct FavoriteMedia {
+    /// AV号
+    pub id: u64,
+    /// 内容类型，2为视频
+    #[serde(rename(deserialize = "type"))]
+    pub media_type: i32,
+    pub title: String,
+    /// 封面
+    pub cover: String,
+    pub bvid: String,
+    /// 失效标志，最低位为1时视频已失效
+    pub attr: i32,
+}
+
+impl FavoriteMedia {
+    fn check(&self) -> Result<()> {
+        if self.media_type != MEDIA_TYPE_VIDEO {
+            Err(VideoSourceError::NoSuchResource(format!(
+                "不支持的收藏类型: id={},type={}",
+                self.id, self.media_type
+            )))
+        } else if self.attr & 1 != 0 {
+            Err(VideoSourceError::NoSuchResource(format!(
+                "视频已失效: bvid={},title={}",
+                self.bvid, self.title
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{
+        test::{fixture, fixture_routes, mock_api},
+        DimensionCode,
+    };
+    use crate::error::VideoSourceError;
+    use crate::source::mock::{MockResponse, MockRoutes};
+    use crate::source::{PartSelection, ResolveOptions, StreamPreference, VideoSource, VideoType};
+    use futures::StreamExt;
+    use reqwest::Url;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// `pages`记录请求的页码，`details`记录请求详情的视频
+    fn favorite_routes(
+        pages: Arc<Mutex<Vec<String>>>,
+        details: Arc<Mutex<Vec<String>>>,
+    ) -> MockRoutes {
+        fixture_routes(&[("/x/player/playurl", "playurl_durl.json")])
+            .route("/x/v3/fav/resource/list", move |request| {
+                let page = request.query("pn").unwrap_or_default();
+                pages.lock().unwrap().push(page.clone());
+                match (request.query("media_id").as_deref(), page.as_str()) {
+                    (Some("1052622027"), "1") => MockResponse::json(fixture("favorite_page1.json")),
+                    (Some("1052622027"), "2") => MockResponse::json(fixture("favorite_page2.json")),
+                    // 空页仍返回`has_more`
+                    (Some("1"), _) => MockResponse::json(
+                        r#"{"code":0,"message":"0","data":{"medias":null,"has_more":true}}"#,
+                    ),
+                    _ => MockResponse::status(404),
+                }
+            })
+            .route("/x/web-interface/view/detail", move |request| {
+                let bvid = request.query("bvid").unwrap_or_default();
+                details.lock().unwrap().push(bvid.clone());
+                match Some(bvid.as_str()) {
+                    Some("BV1ex411J7GE") => MockResponse::json(fixture("view_detail.json")),
+                    _ => MockResponse::json(fixture("view_detail_single.json")),
+                }
+            })
+    }
+
+    #[tokio::test]
+    async fn favorite_list_concurrency_test() {
+        let delay = Duration::from_millis(100);
+        let routes = MockRoutes::new()
+            .route("/x/v3/fav/resource/list", |request| {
+                match request.query("pn").as_deref() {
+                    Some("1") => MockResponse::json(fixture("favorite_page1.json")),
+                    _ => MockResponse::json(fixture("favorite_page2.json")),
+                }
+            })
+            .route("/x/web-interface/view/detail", move |request| {
+                match request.query("bvid").as_deref() {
+                    Some("BV1ex411J7GE") => MockResponse::json(fixture("view_detail.json")),
+                    _ => MockResponse::json(fixture("view_detail_single.json")),
+                }
+                .with_delay(delay)
+            })
+            .route("/x/player/playurl", move |_| {
+                MockResponse::json(fixture("playurl_durl.json")).with_delay(delay)
+            });
+        let (server, mut source) = mock_api(routes).await;
+        source.set_resolve_options(ResolveOptions {
+            concurrency: 2,
+            interval: Duration::from_millis(0),
+        });
+        let url = Url::parse("https://space.bilibili.com/2/favlist?fid=1052622027").unwrap();
+        server.reset_peak();
+        let videos: Vec<_> = source
+            .video_list(
+                &url,
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(videos.len(), 5);
+        // 展开与获取音视频流共用并发数
+        assert_eq!(server.peak_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn favorite_list_test() {
+        let pages = Arc::new(Mutex::new(Vec::new()));
+        let details = Arc::new(Mutex::new(Vec::new()));
+        let (_server, source) = mock_api(favorite_routes(pages.clone(), details.clone())).await;
+        let url = Url::parse("https://space.bilibili.com/2/favlist?fid=1052622027").unwrap();
+        let videos: Vec<_> = source
+            .video_list(
+                &url,
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(videos.len(), 5);
+        let titles: Vec<_> = videos
+            .iter()
+            .filter_map(|video| video.as_ref().ok())
+            .map(|video| video.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                "【动画】火柴人VS动画师 00. 宣传短片",
+                "【动画】火柴人VS动画师 01. 火柴人与动画师",
+                "【动画】火柴人VS动画师 02. 火柴人与动画师 II",
+                "【MV】保加利亚妖王AZIS视频合辑",
+            ]
+        );
+        // 失效的视频
+        let error = videos[3].as_ref().unwrap_err();
+        assert!(error.is_entry());
+        assert!(matches!(error.inner(), VideoSourceError::NoSuchResource(_)));
+        assert!(videos[4].as_ref().unwrap().pic.is_some());
+
+        let videos: Vec<_> = source
+            .video_list(
+                &url,
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &"3".parse().unwrap(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(
+            videos[0].as_ref().unwrap().title,
+            "【MV】保加利亚妖王AZIS视频合辑"
+        );
+
+        // 选择的视频都在第一页时不再请求后续页，列出时不请求视频详情
+        pages.lock().unwrap().clear();
+        details.lock().unwrap().clear();
+        let entries: Vec<_> = source
+            .entries(&url, &"1".parse().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect()
+            .await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "【动画】火柴人VS动画师");
+        assert_eq!(entries[0].metadata.id.as_deref(), Some("BV1ex411J7GE"));
+        assert_eq!(*pages.lock().unwrap(), vec!["1"]);
+        assert!(details.lock().unwrap().is_empty());
+
+        // 展开为各分P
+        let parts = source.expand_entry(&entries[0]).unwrap().await.unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].title, "【动画】火柴人VS动画师 00. 宣传短片");
+        assert_eq!(parts[0].metadata.part, Some(1));
+        assert!(source.expand_entry(&parts[0]).is_none());
+        assert_eq!(*details.lock().unwrap(), vec!["BV1ex411J7GE"]);
+
+        // 未展开时只有单P视频可以直接获取音视频流
+        let (quality, preference) = (DimensionCode::P480.quality(), StreamPreference::default());
+        let resolve = |entry| source.resolve_entry(entry, VideoType::Flv, &quality, &preference);
+        let result = resolve(&entries[0]).await;
+        assert!(matches!(result, Err(VideoSourceError::InvalidEntry(_))));
+        let single: Vec<_> = source
+            .entries(&url, &"3".parse().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect()
+            .await;
+        let video = resolve(&single[0]).await.unwrap();
+        assert_eq!(video.title, "【MV】保加利亚妖王AZIS视频合辑");
+        assert_eq!(video.video.len(), 1);
+
+        // 空页时结束
+        pages.lock().unwrap().clear();
+        let url = Url::parse("https://space.bilibili.com/2/favlist?fid=1").unwrap();
+        let entries: Vec<_> = source
+            .entries(&url, &PartSelection::all())
+            .unwrap()
+            .collect()
+            .await;
+        assert!(entries.is_empty());
+        assert_eq!(*pages.lock().unwrap(), vec!["1"]);
+    }
+}
@@ -0,0 +1,223 @@
+//! FLV解析，用于在关键帧处分段
+
+use crate::error::VideoSourceError;
+use crate::source::Result;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+/// 文件头（9字节）与首个PreviousTagSize（4字节）
+const HEADER_SIZE: usize = 13;
+/// Tag头长度
+const TAG_HEADER_SIZE: usize = 11;
+/// Tag后的PreviousTagSize长度
+const PREVIOUS_TAG_SIZE: usize = 4;
+
+const TAG_AUDIO: u8 = 8;
+const TAG_VIDEO: u8 = 9;
+const TAG_SCRIPT: u8 = 18;
+
+/// FLV Tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct FlvTag {
+    pub tag_type: u8,
+    /// 毫秒
+    pub timestamp: u32,
+    pub data: Bytes,
+}
+
+impl FlvTag {
+    pub fn is_script(&self) -> bool {
+        self.tag_type == TAG_SCRIPT
+    }
+
+    pub fn is_video(&self) -> bool {
+        self.tag_type == TAG_VIDEO
+    }
+
+    pub fn is_audio(&self) -> bool {
+        self.tag_type == TAG_AUDIO
+    }
+
+    pub fn is_keyframe(&self) -> bool {
+        self.is_video() && self.data.first().is_some_and(|flags| flags >> 4 == 1)
+    }
+
+    /// AVC/HEVC的解码配置或AAC的AudioSpecificConfig，每个文件开头都需要
+    pub fn is_sequence_header(&self) -> bool {
+        match (self.tag_type, self.data.first(), self.data.get(1)) {
+            // 7: AVC, 12: HEVC
+            (TAG_VIDEO, Some(flags), Some(0)) => matches!(flags & 0x0f, 7 | 12),
+            // 10: AAC
+            (TAG_AUDIO, Some(flags), Some(0)) => flags >> 4 == 10,
+            _ => false,
+        }
+    }
+
+    /// 以指定时间戳编码，包含Tag后的PreviousTagSize
+    pub fn encode(&self, timestamp: u32) -> Vec<u8> {
+        let size = self.data.len() as u32;
+        let mut tag = Vec::with_capacity(TAG_HEADER_SIZE + self.data.len() + PREVIOUS_TAG_SIZE);
+        tag.push(self.tag_type);
+        tag.extend_from_slice(&size.to_be_bytes()[1..]);
+        tag.extend_from_slice(&timestamp.to_be_bytes()[1..]);
+        tag.push((timestamp >> 24) as u8);
+        tag.extend_from_slice(&[0, 0, 0]);
+        tag.extend_from_slice(&self.data);
+        tag.extend_from_slice(&(TAG_HEADER_SIZE as u32 + size).to_be_bytes());
+        tag
+    }
+}
+
+/// 增量解析FLV数据
+#[derive(Debug, Default)]
+pub(super) struct FlvReader {
+    buffer: BytesMut,
+    header: Option<Bytes>,
+}
+
+impl FlvReader {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// 文件头，包含首个PreviousTagSize
+    pub fn header(&self) -> Option<&Bytes> {
+        self.header.as_ref()
+    }
+
+    /// 取出下一个完整的Tag，数据不足时返回`None`
+    pub fn next_tag(&mut self) -> Result<Option<FlvTag>> {
+        if self.header.is_none() {
+            if self.buffer.len() < HEADER_SIZE {
+                return Ok(None);
+            }
+            if &self.buffer[..3] != b"FLV" {
+                return Err(VideoSourceError::InvalidApiData(
+                    "无效的FLV文件头".to_string(),
+                ));
+            }
+            self.header = Some(self.buffer.split_to(HEADER_SIZE).freeze());
+        }
+        if self.buffer.len() < TAG_HEADER_SIZE {
+            return Ok(None);
+        }
+        let data_size = u32::from_be_bytes([0, self.buffer[1], self.buffer[2], self.buffer[3]]);
+        let tag_size = TAG_HEADER_SIZE + data_size as usize + PREVIOUS_TAG_SIZE;
+        if self.buffer.len() < tag_size {
+            return Ok(None);
+        }
+        let mut tag = self.buffer.split_to(tag_size);
+        let tag_type = tag[0] & 0x1f;
+        let timestamp = u32::from_be_bytes([tag[7], tag[4], tag[5], tag[6]]);
+        tag.advance(TAG_HEADER_SIZE);
+        tag.truncate(data_size as usize);
+        Ok(Some(FlvTag {
+            tag_type,
+            timestamp,
+            data: tag.freeze(),
+        }))
+    }
+}
+
+/// 构造测试用的FLV数据
+#[cfg(test)]
+pub(super) mod sample {
+    use super::{FlvReader, FlvTag, TAG_AUDIO, TAG_SCRIPT, TAG_VIDEO};
+    use bytes::Bytes;
+
+    pub const HEADER: [u8; 13] = [b'F', b'L', b'V', 1, 5, 0, 0, 0, 9, 0, 0, 0, 0];
+
+    fn tag(tag_type: u8, timestamp: u32, data: &[u8]) -> FlvTag {
+        FlvTag {
+            tag_type,
+            timestamp,
+            data: Bytes::copy_from_slice(data),
+        }
+    }
+
+    pub fn metadata() -> FlvTag {
+        tag(TAG_SCRIPT, 0, b"\x02\x00\x0aonMetaData")
+    }
+
+    pub fn video_header() -> FlvTag {
+        tag(TAG_VIDEO, 0, &[0x17, 0, 0, 0, 0, 1, 0x64])
+    }
+
+    pub fn audio_header() -> FlvTag {
+        tag(TAG_AUDIO, 0, &[0xaf, 0, 0x12, 0x10])
+    }
+
+    pub fn keyframe(timestamp: u32) -> FlvTag {
+        tag(TAG_VIDEO, timestamp, &[0x17, 1, 0, 0, 0, 0xaa, 0xbb])
+    }
+
+    pub fn frame(timestamp: u32) -> FlvTag {
+        tag(TAG_VIDEO, timestamp, &[0x27, 1, 0, 0, 0, 0xcc])
+    }
+
+    pub fn audio(timestamp: u32) -> FlvTag {
+        tag(TAG_AUDIO, timestamp, &[0xaf, 1, 0xdd, 0xee])
+    }
+
+    /// 从`start`（毫秒）开始，每500毫秒一帧，每1000毫秒一个关键帧
+    pub fn stream(start: u32, frames: u32) -> Vec<u8> {
+        let mut data = HEADER.to_vec();
+        for header in [metadata(), video_header(), audio_header()] {
+            data.extend(header.encode(0));
+        }
+        for index in 0..frames {
+            let timestamp = start + index * 500;
+            let video = if index % 2 == 0 {
+                keyframe(timestamp)
+            } else {
+                frame(timestamp)
+            };
+            data.extend(video.encode(timestamp));
+            data.extend(audio(timestamp).encode(timestamp));
+        }
+        data
+    }
+
+    pub fn read_tags(data: &[u8]) -> Vec<FlvTag> {
+        let mut reader = FlvReader::default();
+        reader.push(data);
+        let mut tags = Vec::new();
+        while let Some(tag) = reader.next_tag().unwrap() {
+            tags.push(tag);
+        }
+        tags
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sample, FlvReader};
+
+    #[test]
+    fn flv_reader_test() {
+        let data = sample::stream(0x0100_0000, 4);
+        let mut reader = FlvReader::default();
+        let mut tags = Vec::new();
+        // 按任意长度分块输入
+        for chunk in data.chunks(7) {
+            reader.push(chunk);
+            while let Some(tag) = reader.next_tag().unwrap() {
+                tags.push(tag);
+            }
+        }
+        assert_eq!(reader.header().unwrap().as_ref(), &sample::HEADER);
+        assert_eq!(tags.len(), 3 + 8);
+        assert!(tags[0].is_script());
+        assert!(tags[1].is_sequence_header() && tags[1].is_video());
+        assert!(tags[2].is_sequence_header() && tags[2].is_audio());
+        assert_eq!(tags[3], sample::keyframe(0x0100_0000));
+        assert!(tags[3].is_keyframe() && !tags[3].is_sequence_header());
+        assert!(!tags[5].is_keyframe());
+        assert_eq!(tags[10].timestamp, 0x0100_0000 + 1500);
+        assert_eq!(sample::read_tags(&data), tags);
+
+        let mut reader = FlvReader::default();
+        reader.push(b"<html></html>");
+        assert!(reader.next_tag().is_err());
+    }
+}
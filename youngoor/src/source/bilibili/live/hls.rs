@@ -0,0 +1,217 @@
+//! HLS直播流：轮询播放列表，按顺序下载新分片
+
+use super::super::BilibiliClient;
+use crate::error::VideoSourceError;
+use crate::source::Result;
+
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use reqwest::Url;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// 轮询播放列表的最短间隔
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 媒体播放列表
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Playlist {
+    /// 分片最长时长，秒
+    pub target_duration: f64,
+    /// 第一个分片的序号
+    pub media_sequence: u64,
+    /// fMP4的初始化分片
+    pub map: Option<String>,
+    pub segments: Vec<PlaylistSegment>,
+    /// 直播已结束
+    pub end: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct PlaylistSegment {
+    pub uri: String,
+    /// 秒
+    pub duration: f64,
+}
+
+impl Playlist {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some("#EXTM3U") {
+            return Err(VideoSourceError::InvalidApiData(
+                "无效的HLS播放列表".to_string(),
+            ));
+        }
+        let mut playlist = Self::default();
+        let mut duration = 0.0;
+        for line in lines {
+            if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+                playlist.target_duration = value.parse().unwrap_or_default();
+            } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+                playlist.media_sequence = value.parse().unwrap_or_default();
+            } else if let Some(value) = line.strip_prefix("#EXT-X-MAP:") {
+                playlist.map = Self::attribute(value, "URI");
+            } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+                duration = value
+                    .split(',')
+                    .next()
+                    .and_then(|duration| duration.parse().ok())
+                    .unwrap_or_default();
+            } else if line == "#EXT-X-ENDLIST" {
+                playlist.end = true;
+            } else if !line.starts_with('#') {
+                playlist.segments.push(PlaylistSegment {
+                    uri: line.to_string(),
+                    duration,
+                });
+                duration = 0.0;
+            }
+        }
+        Ok(playlist)
+    }
+
+    /// 取`KEY="VALUE",...`形式的属性
+    fn attribute(attributes: &str, name: &str) -> Option<String> {
+        attributes
+            .split(',')
+            .filter_map(|attribute| attribute.split_once('='))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim().trim_matches('"').to_string())
+    }
+}
+
+/// 下载的分片
+#[derive(Debug)]
+pub(super) struct HlsSegment {
+    /// fMP4的初始化分片，每个文件开头都需要
+    pub init: bool,
+    /// 秒，初始化分片为0
+    pub duration: f64,
+    pub data: Bytes,
+}
+
+impl BilibiliClient {
+    /// 轮询播放列表并依次返回新分片，直到播放列表结束或请求失败
+    ///
+    /// 使用`urls`中第一个可用的播放列表地址，请求超过`stall_timeout`时以错误结束
+    pub(super) fn hls_segments<'a>(
+        &'a self,
+        urls: &'a [Url],
+        stall_timeout: Duration,
+    ) -> BoxStream<'a, Result<HlsSegment>> {
+        use async_stream::try_stream;
+
+        Box::pin(try_stream! {
+            let (playlist_url, response) = self.request_live_mirrors(urls, stall_timeout).await?;
+            let mut text =
+                Self::live_timeout(stall_timeout, &playlist_url, response.text()).await?;
+            let mut next_sequence = None;
+            // 上一次播放列表中的分片
+            let mut seen = HashSet::new();
+            let mut map = None;
+            loop {
+                let playlist = Playlist::parse(&text)?;
+                if let Some(uri) = &playlist.map {
+                    let uri = Self::join_url(&playlist_url, uri)?;
+                    if map.as_ref() != Some(&uri) {
+                        let data = self.request_live_data(&uri, stall_timeout).await?;
+                        yield HlsSegment {
+                            init: true,
+                            duration: 0.0,
+                            data,
+                        };
+                        map = Some(uri);
+                    }
+                }
+                // 序号回退且有未见过的分片时，直播流已重新开始，按新的序号继续
+                let restarted = next_sequence.is_some_and(|next| {
+                    (playlist.media_sequence..)
+                        .zip(&playlist.segments)
+                        .any(|(sequence, segment)| {
+                            sequence < next && !seen.contains(&segment.uri)
+                        })
+                });
+                if restarted {
+                    next_sequence = None;
+                }
+                for (sequence, segment) in (playlist.media_sequence..).zip(&playlist.segments) {
+                    if seen.contains(&segment.uri)
+                        || next_sequence.is_some_and(|next| sequence < next)
+                    {
+                        continue;
+                    }
+                    let uri = Self::join_url(&playlist_url, &segment.uri)?;
+                    let data = self.request_live_data(&uri, stall_timeout).await?;
+                    yield HlsSegment {
+                        init: false,
+                        duration: segment.duration,
+                        data,
+                    };
+                    next_sequence = Some(sequence + 1);
+                }
+                seen = playlist.segments.into_iter().map(|segment| segment.uri).collect();
+                if playlist.end {
+                    break;
+                }
+                let interval = Duration::from_secs_f64(playlist.target_duration.max(0.0) / 2.0);
+                tokio::time::sleep(interval.max(MIN_POLL_INTERVAL)).await;
+                let response = self.request_live(&playlist_url);
+                let response = Self::live_timeout(stall_timeout, &playlist_url, response).await?;
+                text = Self::live_timeout(stall_timeout, &playlist_url, response.text()).await?;
+            }
+        })
+    }
+
+    /// 下载分片，连接或接收超过`stall_timeout`时视为停滞
+    async fn request_live_data(&self, url: &Url, stall_timeout: Duration) -> Result<Bytes> {
+        let response = Self::live_timeout(stall_timeout, url, self.request_live(url)).await?;
+        Self::live_timeout(stall_timeout, url, response.bytes()).await
+    }
+
+    fn join_url(base: &Url, uri: &str) -> Result<Url> {
+        base.join(uri)
+            .map_err(|_| VideoSourceError::InvalidApiData(format!("无效的分片地址: {}", uri)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Playlist, PlaylistSegment};
+
+    #[test]
+    fn playlist_parse_test() {
+        let playlist = Playlist::parse(
+            "#EXTM3U\n\
+             #EXT-X-VERSION:7\n\
+             #EXT-X-TARGETDURATION:1\n\
+             #EXT-X-MEDIA-SEQUENCE:1024\n\
+             #EXT-X-MAP:URI=\"h1626.m4s\"\n\
+             #EXTINF:1.00,a|b\n\
+             1024.m4s\n\
+             #EXTINF:0.98,\n\
+             1025.m4s?trid=1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            playlist,
+            Playlist {
+                target_duration: 1.0,
+                media_sequence: 1024,
+                map: Some("h1626.m4s".to_string()),
+                segments: vec![
+                    PlaylistSegment {
+                        uri: "1024.m4s".to_string(),
+                        duration: 1.0
+                    },
+                    PlaylistSegment {
+                        uri: "1025.m4s?trid=1".to_string(),
+                        duration: 0.98
+                    },
+                ],
+                end: false,
+            }
+        );
+        assert!(Playlist::parse("#EXTM3U\n#EXT-X-ENDLIST").unwrap().end);
+        assert!(Playlist::parse("<html></html>").is_err());
+    }
+}
@@ -0,0 +1,793 @@
+//! 直播间：房间号解析、直播流地址与录制
+
+mod flv;
+mod hls;
+mod recorder;
+
+pub use recorder::{RecordOptions, SplitPolicy};
+
+use super::{BilibiliClient, BilibiliSource};
+use crate::cookie::{CookieJar, CookieWarning};
+use crate::error::VideoSourceError;
+use crate::source::{
+    EntryKey, PartSelection, Quality, Result, StreamPreference, VideoEntry, VideoEntryStream,
+    VideoInfo, VideoMetadata, VideoSource, VideoType,
+};
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::{header::REFERER, Url};
+use serde::Deserialize;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const REQUEST_ROOM_INIT_URL: &str = "https://api.live.bilibili.com/room/v1/Room/room_init";
+const REQUEST_ROOM_INFO_URL: &str = "https://api.live.bilibili.com/room/v1/Room/get_info";
+const REQUEST_PLAY_INFO_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo";
+/// 直播CDN要求的来源
+const LIVE_REFERER: &str = "https://live.bilibili.com/";
+/// 直播状态：直播中
+const LIVE_STATUS_LIVE: i32 = 1;
+
+/// 直播画质代码与对应的高度，原画的实际高度取决于主播，按高码率1080P处理
+const LIVE_QUALITIES: [(i32, u32, &str); 7] = [
+    (80, 480, "流畅"),
+    (150, 720, "高清"),
+    (250, 720, "超清"),
+    (400, 1080, "蓝光"),
+    (10000, 1080, "原画"),
+    (20000, 2160, "4K"),
+    (30000, 2160, "杜比"),
+];
+
+/// 直播画质代码对应的清晰度，未知代码的高度为0
+fn live_quality(qn: i32) -> Quality {
+    match LIVE_QUALITIES.iter().find(|(code, _, _)| *code == qn) {
+        Some(&(code, height, label)) => Quality {
+            high_bitrate: code == 250 || code == 10000,
+            dolby: code == 30000,
+            ..Quality::new(height, label)
+        },
+        None => Quality::new(0, &format!("未知画质（{}）", qn)),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BilibiliLiveSource(BilibiliClient);
+
+/// 直播流协议
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum LiveProtocol {
+    /// HTTP-FLV，单个连接持续返回数据
+    Flv,
+    /// HLS，轮询播放列表下载分片，优先使用TS分片
+    Hls,
+}
+
+impl From<VideoType> for LiveProtocol {
+    fn from(video_type: VideoType) -> Self {
+        match video_type {
+            VideoType::Flv => Self::Flv,
+            VideoType::MP4 => Self::Hls,
+        }
+    }
+}
+
+/// 直播间信息
+#[derive(Debug, Clone)]
+pub struct LiveRoom {
+    /// 真实房间号
+    pub room_id: u64,
+    /// 短号，没有时为0
+    pub short_id: u64,
+    /// 主播
+    pub uid: u64,
+    pub title: String,
+    /// 封面
+    pub cover: Option<Url>,
+    /// 简介
+    pub description: String,
+    /// 标签
+    pub tags: Vec<String>,
+    /// 是否正在直播
+    pub live: bool,
+}
+
+/// 直播流地址
+#[derive(Debug, Clone)]
+pub struct LiveStream {
+    pub room_id: u64,
+    pub protocol: LiveProtocol,
+    /// 封装格式：`flv`、`ts`或`fmp4`
+    pub format: String,
+    /// 实际画质，可能低于请求的画质
+    pub quality: i32,
+    pub quality_description: String,
+    /// 可用的画质
+    pub accept_quality: Vec<i32>,
+    /// 各CDN的地址，内容相同
+    pub urls: Vec<Url>,
+}
+
+impl LiveStream {
+    /// 录制文件的扩展名
+    pub fn extension(&self) -> &'static str {
+        match self.format.as_str() {
+            "ts" => "ts",
+            "fmp4" => "mp4",
+            _ => "flv",
+        }
+    }
+}
+
+impl BilibiliClient {
+    async fn request_room_init(&self, room_id: u64) -> Result<RoomInit> {
+        let url = self.api_url(REQUEST_ROOM_INIT_URL)?;
+        self.bilibili_http_get_not_null(&url, [("id", room_id.to_string())].iter(), false)
+            .await
+    }
+
+    async fn request_room_info(&self, room_id: u64) -> Result<RoomInfo> {
+        let url = self.api_url(REQUEST_ROOM_INFO_URL)?;
+        self.bilibili_http_get_not_null(&url, [("room_id", room_id.to_string())].iter(), false)
+            .await
+    }
+
+    /// `room_id`须为真实房间号
+    async fn request_play_info(
+        &self,
+        room_id: u64,
+        quality: i32,
+        protocol: LiveProtocol,
+    ) -> Result<LiveStream> {
+        let url = self.api_url(REQUEST_PLAY_INFO_URL)?;
+        let query_params = [
+            ("room_id", room_id.to_string()),
+            // 0: http_stream, 1: http_hls
+            ("protocol", "0,1".to_string()),
+            // 0: flv, 1: ts, 2: fmp4
+            ("format", "0,1,2".to_string()),
+            // 0: avc, 1: hevc
+            ("codec", "0,1".to_string()),
+            ("qn", quality.to_string()),
+            ("platform", "web".to_string()),
+            ("ptype", "8".to_string()),
+        ];
+        let result: RoomPlayInfo = self
+            .bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await?;
+        let playurl = match result.playurl_info {
+            Some(info) if result.live_status == LIVE_STATUS_LIVE => info.playurl,
+            _ => {
+                return Err(VideoSourceError::NoSuchResource(format!(
+                    "直播间未开播: room_id={}",
+                    room_id
+                )))
+            }
+        };
+        let not_found = || {
+            VideoSourceError::NoSuchResource(format!(
+                "直播流: room_id={},protocol={:?}",
+                room_id, protocol
+            ))
+        };
+        let (protocol_name, format_names): (_, &[&str]) = match protocol {
+            LiveProtocol::Flv => ("http_stream", &["flv"]),
+            LiveProtocol::Hls => ("http_hls", &["ts", "fmp4"]),
+        };
+        let mut formats = playurl
+            .stream
+            .into_iter()
+            .find(|stream| stream.protocol_name == protocol_name)
+            .ok_or_else(not_found)?
+            .format;
+        let format = format_names
+            .iter()
+            .find_map(|name| {
+                formats
+                    .iter()
+                    .position(|format| format.format_name == *name)
+            })
+            .map(|index| formats.swap_remove(index))
+            .ok_or_else(not_found)?;
+        let mut codecs = format.codec;
+        if codecs.is_empty() {
+            return Err(not_found());
+        }
+        // 优先使用兼容性更好的AVC
+        let index = codecs
+            .iter()
+            .position(|codec| codec.codec_name == "avc")
+            .unwrap_or(0);
+        let codec = codecs.swap_remove(index);
+        let urls = codec
+            .url_info
+            .iter()
+            .map(|info| {
+                BilibiliSource::parse_url(&format!("{}{}{}", info.host, codec.base_url, info.extra))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if urls.is_empty() {
+            return Err(not_found());
+        }
+        let quality_description = playurl
+            .g_qn_desc
+            .into_iter()
+            .find(|desc| desc.qn == codec.current_qn)
+            .map(|desc| desc.desc)
+            .unwrap_or_default();
+        Ok(LiveStream {
+            room_id,
+            protocol,
+            format: format.format_name,
+            quality: codec.current_qn,
+            quality_description,
+            accept_quality: codec.accept_qn,
+            urls,
+        })
+    }
+
+    /// 请求直播CDN上的资源
+    async fn request_live(&self, url: &Url) -> Result<reqwest::Response> {
+        Self::http_request(self.client.get(url.clone()).header(REFERER, LIVE_REFERER)).await
+    }
+
+    /// 依次尝试各CDN，返回第一个可用的连接，连接超过`stall_timeout`时尝试下一个
+    async fn request_live_mirrors(
+        &self,
+        urls: &[Url],
+        stall_timeout: Duration,
+    ) -> Result<(Url, reqwest::Response)> {
+        let mut last_error = None;
+        for url in urls {
+            match Self::live_timeout(stall_timeout, url, self.request_live(url)).await {
+                Ok(response) => return Ok((url.clone(), response)),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| VideoSourceError::NoSuchResource("直播流".to_string())))
+    }
+
+    /// 等待直播CDN的响应或数据，超过`stall_timeout`时视为停滞
+    async fn live_timeout<T, E>(
+        stall_timeout: Duration,
+        url: &Url,
+        future: impl Future<Output = std::result::Result<T, E>>,
+    ) -> Result<T>
+    where
+        VideoSourceError: From<E>,
+    {
+        match timeout(stall_timeout, future).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(VideoSourceError::RequestError(format!(
+                "直播流停滞: {}",
+                url
+            ))),
+        }
+    }
+}
+
+impl BilibiliLiveSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求直播间信息，`room_id`可以是短号
+    pub async fn room(&self, room_id: u64) -> Result<LiveRoom> {
+        let init = self.0.request_room_init(room_id).await?;
+        let info = self.0.request_room_info(init.room_id).await?;
+        Ok(LiveRoom {
+            room_id: init.room_id,
+            short_id: init.short_id,
+            uid: init.uid,
+            title: info.title,
+            cover: Url::parse(&info.user_cover).ok(),
+            description: info.description,
+            tags: info
+                .tags
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+            live: init.live_status == LIVE_STATUS_LIVE,
+        })
+    }
+
+    /// 请求直播流地址，`room_id`须为真实房间号
+    ///
+    /// 请求的画质不可用时返回可用的最高画质，实际画质见`LiveStream::quality`
+    pub async fn live_stream(
+        &self,
+        room_id: u64,
+        quality: i32,
+        protocol: LiveProtocol,
+    ) -> Result<LiveStream> {
+        self.0.request_play_info(room_id, quality, protocol).await
+    }
+
+    /// 直播数据，直到服务器断开连接
+    ///
+    /// FLV为连接上的原始数据；HLS为依次下载的分片，fMP4的初始化分片在最前。
+    /// 连接或接收数据超过`stall_timeout`时以错误结束
+    pub fn live_data<'a>(
+        &'a self,
+        stream: &'a LiveStream,
+        stall_timeout: Duration,
+    ) -> BoxStream<'a, Result<Bytes>> {
+        use async_stream::try_stream;
+
+        Box::pin(try_stream! {
+            match stream.protocol {
+                LiveProtocol::Flv => {
+                    let (url, response) =
+                        self.0.request_live_mirrors(&stream.urls, stall_timeout).await?;
+                    let mut data = response.bytes_stream();
+                    while let Some(chunk) =
+                        BilibiliClient::live_timeout(stall_timeout, &url, async {
+                            data.next().await.transpose()
+                        })
+                        .await?
+                    {
+                        yield chunk;
+                    }
+                }
+                LiveProtocol::Hls => {
+                    let mut segments = self.0.hls_segments(&stream.urls, stall_timeout);
+                    while let Some(segment) = segments.next().await {
+                        yield segment?.data;
+                    }
+                }
+            }
+        })
+    }
+
+    fn room_id(url: &Url) -> Option<u64> {
+        if !url.host_str()?.eq_ignore_ascii_case("live.bilibili.com") {
+            return None;
+        }
+        let mut path = url.path_segments()?;
+        match path.next()? {
+            "h5" | "blanc" => path.next()?.parse().ok(),
+            id => id.parse().ok(),
+        }
+    }
+}
+
+impl VideoSource for BilibiliLiveSource {
+    fn pretty_name(&self) -> &'static str {
+        "bilibili-live"
+    }
+
+    /// 直播间作为唯一的条目，`parts`对直播无效
+    fn entries(&self, url: &Url, _parts: &PartSelection) -> Result<VideoEntryStream<'_>> {
+        use async_stream::try_stream;
+
+        let room_id =
+            Self::room_id(url).ok_or_else(|| VideoSourceError::InvalidUrl(url.clone()))?;
+        Ok(Box::pin(try_stream! {
+            let room = self.room(room_id).await?;
+            yield VideoEntry {
+                pic: room.cover,
+                title: room.title,
+                lyric: None,
+                metadata: VideoMetadata {
+                    id: Some(room.room_id.to_string()),
+                    description: Some(room.description).filter(|description| !description.is_empty()),
+                    tags: room.tags,
+                    ..VideoMetadata::default()
+                },
+                key: EntryKey::new(room.room_id),
+            }
+        }))
+    }
+
+    /// 返回当前直播流的地址，`preference`对直播无效
+    fn resolve_entry<'a>(
+        &'a self,
+        entry: &'a VideoEntry,
+        video_type: VideoType,
+        quality: &'a Quality,
+        preference: &'a StreamPreference,
+    ) -> BoxFuture<'a, Result<VideoInfo>> {
+        Box::pin(async move {
+            let room_id = *entry
+                .key
+                .get::<u64>()
+                .ok_or_else(|| VideoSourceError::InvalidEntry(entry.key.to_string()))?;
+            let qn = quality
+                .code_in(
+                    LIVE_QUALITIES
+                        .iter()
+                        .map(|&(code, _, _)| (code, live_quality(code))),
+                )
+                .unwrap_or(LIVE_QUALITIES[0].0);
+            let stream = self.live_stream(room_id, qn, video_type.into()).await?;
+            Ok(VideoInfo {
+                video: stream.urls.into_iter().take(1).collect(),
+                quality: Some(live_quality(stream.quality)),
+                ..entry.clone().into_video(video_type, quality, preference)
+            })
+        })
+    }
+
+    fn valid(&self, url: &Url) -> bool {
+        Self::room_id(url).is_some()
+    }
+
+    fn set_token(&mut self, token: String) {
+        self.0.set_token(token)
+    }
+
+    fn token(&self) -> Option<&str> {
+        self.0.token()
+    }
+
+    fn cookies(&self) -> Option<&CookieJar> {
+        Some(&self.0.cookies)
+    }
+
+    fn import_cookies(&mut self, cookies: CookieJar) -> Vec<CookieWarning> {
+        self.0.import_cookies(cookies)
+    }
+
+    fn qualities(&self) -> Vec<Quality> {
+        LIVE_QUALITIES
+            .iter()
+            .map(|&(code, _, _)| live_quality(code))
+            .collect()
+    }
+}
+
+/// 房间号解析结果
+#[derive(Debug, Deserialize)]
+struct RoomInit {
+    pub room_id: u64,
+    pub short_id: u64,
+    pub uid: u64,
+    /// 0: 未开播，1: 直播中，2: 轮播中
+    pub live_status: i32,
+}
+
+/// 直播间详情
+#[derive(Debug, Deserialize)]
+struct RoomInfo {
+    pub title: String,
+    /// 封面
+    pub user_cover: String,
+    /// 简介
+    #[serde(default)]
+    pub description: String,
+    /// 以逗号分隔的标签
+    #[serde(default)]
+    pub tags: String,
+}
+
+/// 直播流信息
+#[derive(Debug, Deserialize)]
+struct RoomPlayInfo {
+    pub live_status: i32,
+    /// 未开播时为`null`
+    pub playurl_info: Option<PlayurlInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayurlInfo {
+    pub playurl: Playurl,
+}
+
+#[derive(Debug, Deserialize)]
+struct Playurl {
+    /// 画质说明
+    pub g_qn_desc: Vec<QualityDescription>,
+    pub stream: Vec<StreamProtocol>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QualityDescription {
+    pub qn: i32,
+    pub desc: String,
+}
+
+/// 协议：`http_stream`或`http_hls`
+#[derive(Debug, Deserialize)]
+struct StreamProtocol {
+    pub protocol_name: String,
+    pub format: Vec<StreamFormat>,
+}
+
+/// 封装格式：`flv`、`ts`或`fmp4`
+#[derive(Debug, Deserialize)]
+struct StreamFormat {
+    pub format_name: String,
+    pub codec: Vec<StreamCodec>,
+}
+
+/// 编码：`avc`或`hevc`
+#[derive(Debug, Deserialize)]
+struct StreamCodec {
+    pub codec_name: String,
+    pub current_qn: i32,
+    pub accept_qn: Vec<i32>,
+    pub base_url: String,
+    pub url_info: Vec<StreamUrlInfo>,
+}
+
+/// CDN，完整地址为`host + base_url + extra`
+#[derive(Debug, Deserialize)]
+struct StreamUrlInfo {
+    pub host: String,
+    pub extra: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::fixture;
+    use super::{flv::sample, live_quality, BilibiliLiveSource, LiveProtocol};
+    use crate::error::VideoSourceError;
+    use crate::source::mock::{MockRequest, MockResponse, MockServer};
+    use crate::source::{PartSelection, StreamPreference, VideoSource, VideoType};
+    use futures::StreamExt;
+    use reqwest::Url;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    /// 本地直播服务器的状态
+    #[derive(Debug, Default)]
+    pub(super) struct LiveServerState {
+        /// 直播流被请求的次数
+        pub connections: AtomicUsize,
+        /// 直播流请求达到该次数后下播
+        pub max_connections: usize,
+        /// HLS播放列表被请求的次数
+        pub playlist_requests: AtomicUsize,
+        /// 直播流返回404
+        pub broken: bool,
+        /// FLV只发送一半数据后停滞
+        pub stall: bool,
+        /// HLS第二次返回的播放列表序号从0重新开始
+        pub restart: bool,
+    }
+
+    impl LiveServerState {
+        fn live(&self) -> bool {
+            self.connections.load(Ordering::SeqCst) < self.max_connections
+        }
+    }
+
+    pub(super) fn live_response(state: &LiveServerState, request: &MockRequest) -> MockResponse {
+        let room_status = |json: String| {
+            let status = if state.live() { "1" } else { "0" };
+            MockResponse::json(json.replace(
+                "\"live_status\": 1",
+                &format!("\"live_status\": {}", status),
+            ))
+        };
+        match request.url.path() {
+            "/room/v1/Room/room_init" => {
+                assert!(matches!(
+                    request.query("id").as_deref(),
+                    Some("6") | Some("5440")
+                ));
+                room_status(fixture("live_room_init.json"))
+            }
+            "/room/v1/Room/get_info" => {
+                assert_eq!(request.query("room_id").as_deref(), Some("5440"));
+                room_status(fixture("live_room_info.json"))
+            }
+            "/xlive/web-room/v2/index/getRoomPlayInfo" => {
+                assert_eq!(request.query("room_id").as_deref(), Some("5440"));
+                let origin = request.url.origin().ascii_serialization();
+                room_status(fixture("live_play_info.json").replace("{host}", &origin))
+            }
+            path if state.broken && path.starts_with("/live-bvc/") => MockResponse::status(404),
+            "/live-bvc/5440.flv" => {
+                assert_eq!(
+                    request.header("referer"),
+                    Some("https://live.bilibili.com/")
+                );
+                let connection = state.connections.fetch_add(1, Ordering::SeqCst) as u32;
+                // 每次连接从不同的时间戳开始，录制时应重置为0
+                let data = sample::stream(connection * 10_000 + 5, 6);
+                if state.stall {
+                    let half = data.len() / 2;
+                    MockResponse::bytes("video/x-flv", data).with_stall(half)
+                } else {
+                    MockResponse::bytes("video/x-flv", data)
+                }
+            }
+            "/live-bvc/5440.m3u8" => {
+                let request = state.playlist_requests.fetch_add(1, Ordering::SeqCst);
+                let playlist = if request.is_multiple_of(2) {
+                    "#EXTM3U\n#EXT-X-TARGETDURATION:0\n#EXT-X-MEDIA-SEQUENCE:10\n\
+                     #EXTINF:1.0,\n10.ts\n#EXTINF:1.0,\n11.ts\n"
+                } else if state.restart {
+                    state.connections.fetch_add(1, Ordering::SeqCst);
+                    "#EXTM3U\n#EXT-X-TARGETDURATION:0\n#EXT-X-MEDIA-SEQUENCE:0\n\
+                     #EXTINF:1.0,\n0.ts\n#EXTINF:1.0,\n1.ts\n#EXT-X-ENDLIST\n"
+                } else {
+                    // 第二次请求时已有新分片，直播流结束
+                    state.connections.fetch_add(1, Ordering::SeqCst);
+                    "#EXTM3U\n#EXT-X-TARGETDURATION:0\n#EXT-X-MEDIA-SEQUENCE:11\n\
+                     #EXTINF:1.0,\n11.ts\n#EXTINF:1.0,\n12.ts\n#EXTINF:1.0,\n13.ts\n#EXT-X-ENDLIST\n"
+                };
+                MockResponse::bytes("application/vnd.apple.mpegurl", playlist.into())
+            }
+            path if path.starts_with("/live-bvc/") && path.ends_with(".ts") => {
+                let name = path.trim_start_matches("/live-bvc/");
+                MockResponse::bytes("video/mp2t", format!("[{}]", name).into_bytes())
+            }
+            _ => MockResponse::status(404),
+        }
+    }
+
+    pub(super) async fn live_server(state: Arc<LiveServerState>) -> MockServer {
+        MockServer::start(move |request| live_response(&state, request)).await
+    }
+
+    pub(super) fn mock_live_source(server: &MockServer) -> BilibiliLiveSource {
+        let mut source = BilibiliLiveSource::default();
+        source.0.origin = Some(server.url("/"));
+        source
+    }
+
+    #[test]
+    fn room_id_test() {
+        for (url, room_id) in [
+            ("https://live.bilibili.com/6", Some(6)),
+            (
+                "https://live.bilibili.com/5440?spm_id_from=333.1007",
+                Some(5440),
+            ),
+            ("https://live.bilibili.com/h5/5440", Some(5440)),
+            ("https://live.bilibili.com/blanc/5440", Some(5440)),
+            ("https://live.bilibili.com/p/eden/area-tags", None),
+            ("https://www.bilibili.com/5440", None),
+        ] {
+            assert_eq!(
+                BilibiliLiveSource::room_id(&Url::parse(url).unwrap()),
+                room_id
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn live_stream_test() {
+        // FLV与HLS各连接一次后下播
+        let state = Arc::new(LiveServerState {
+            max_connections: 2,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state.clone()).await;
+        let source = mock_live_source(&server);
+
+        let room = source.room(6).await.unwrap();
+        assert_eq!((room.room_id, room.short_id, room.uid), (5440, 6, 9617619));
+        assert_eq!(room.title, "测试直播间");
+        assert_eq!(room.tags, vec!["英雄联盟", "娱乐"]);
+        assert!(room.live);
+
+        let stream = source
+            .live_stream(room.room_id, 10000, LiveProtocol::Flv)
+            .await
+            .unwrap();
+        assert_eq!(
+            (stream.quality, stream.quality_description.as_str()),
+            (400, "蓝光")
+        );
+        assert_eq!(stream.accept_quality, vec![400, 250, 150]);
+        assert_eq!(stream.extension(), "flv");
+        // 第一个CDN不可用
+        assert_eq!(stream.urls.len(), 2);
+        let data: Vec<u8> = source
+            .live_data(&stream, Duration::from_secs(5))
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(data, sample::stream(5, 6));
+
+        let stream = source
+            .live_stream(room.room_id, 10000, LiveProtocol::Hls)
+            .await
+            .unwrap();
+        assert_eq!((stream.format.as_str(), stream.extension()), ("ts", "ts"));
+        let data: Vec<u8> = source
+            .live_data(&stream, Duration::from_secs(5))
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(data, b"[10.ts][11.ts][12.ts][13.ts]");
+
+        // 已下播
+        assert!(!source.room(6).await.unwrap().live);
+        assert!(source
+            .live_stream(room.room_id, 10000, LiveProtocol::Flv)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn live_video_list_test() {
+        let state = Arc::new(LiveServerState {
+            max_connections: 1,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state).await;
+        let source = mock_live_source(&server);
+        let url = Url::parse("https://live.bilibili.com/6").unwrap();
+        assert!(source.valid(&url));
+        let videos: Vec<_> = source
+            .video_list(
+                &url,
+                VideoType::Flv,
+                &live_quality(10000),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(videos.len(), 1);
+        let video = videos[0].as_ref().unwrap();
+        assert_eq!(video.title, "测试直播间");
+        assert_eq!(video.metadata.id.as_deref(), Some("5440"));
+        assert_eq!(
+            video.metadata.description.as_deref(),
+            Some("每天晚上八点开播")
+        );
+        assert_eq!(video.video.len(), 1);
+        assert!(video.video[0].path().ends_with("5440.flv"));
+        assert!(video.audio.is_empty());
+    }
+
+    #[tokio::test]
+    async fn live_stall_test() {
+        let state = Arc::new(LiveServerState {
+            max_connections: 1,
+            stall: true,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state).await;
+        let source = mock_live_source(&server);
+        let stream = source
+            .live_stream(5440, 10000, LiveProtocol::Flv)
+            .await
+            .unwrap();
+        let chunks: Vec<_> = source
+            .live_data(&stream, Duration::from_millis(300))
+            .collect()
+            .await;
+        let (last, data) = chunks.split_last().unwrap();
+        assert!(!data.is_empty() && data.iter().all(Result::is_ok));
+        assert!(matches!(last, Err(VideoSourceError::RequestError(_))));
+    }
+
+    #[tokio::test]
+    async fn hls_restart_test() {
+        // 序号回退后下载新的分片
+        let state = Arc::new(LiveServerState {
+            max_connections: 1,
+            restart: true,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state).await;
+        let source = mock_live_source(&server);
+        let stream = source
+            .live_stream(5440, 10000, LiveProtocol::Hls)
+            .await
+            .unwrap();
+        let data: Vec<u8> = source
+            .live_data(&stream, Duration::from_secs(5))
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(data, b"[10.ts][11.ts][0.ts][1.ts]");
+    }
+}
@@ -0,0 +1,503 @@
+//! 直播录制：按时长或大小分段，断流后自动重连
+
+use super::flv::{FlvReader, FlvTag};
+use super::{BilibiliLiveSource, LiveProtocol, LiveStream, LIVE_STATUS_LIVE};
+use crate::error::VideoSourceError;
+use crate::source::Result;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+/// 重连等待时间的上限，`RecordOptions::retry_interval`更长时以其为准
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 分段条件，任一条件满足时开始新文件，均为`None`时不分段
+///
+/// FLV在关键帧处分段，HLS在分片处分段，因此文件会略超过限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SplitPolicy {
+    /// 单个文件的最长时长
+    pub max_duration: Option<Duration>,
+    /// 单个文件的最大字节数
+    pub max_size: Option<u64>,
+}
+
+impl SplitPolicy {
+    fn should_split(&self, duration: Duration, size: u64) -> bool {
+        self.max_duration.is_some_and(|max| duration >= max)
+            || self.max_size.is_some_and(|max| size >= max)
+    }
+}
+
+/// 录制选项
+#[derive(Debug, Clone)]
+pub struct RecordOptions {
+    /// 画质代码，不可用时使用可用的最高画质
+    pub quality: i32,
+    pub protocol: LiveProtocol,
+    pub split: SplitPolicy,
+    /// 连续重连失败的最大次数
+    pub max_retries: u32,
+    /// 重连前的等待时间，连续失败时每次加倍
+    pub retry_interval: Duration,
+    /// 连接或接收数据超过该时间时视为断流
+    pub stall_timeout: Duration,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            quality: 10000,
+            protocol: LiveProtocol::Flv,
+            split: SplitPolicy::default(),
+            max_retries: 5,
+            retry_interval: Duration::from_secs(3),
+            stall_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RecordOptions {
+    /// 连续失败`retries`次后的等待时间，断流后立即重连时也至少等待`retry_interval`
+    fn retry_delay(&self, retries: u32) -> Duration {
+        let factor = 1 << retries.saturating_sub(1).min(16);
+        self.retry_interval
+            .saturating_mul(factor)
+            .min(MAX_RETRY_INTERVAL.max(self.retry_interval))
+    }
+}
+
+/// 录制的文件
+struct Segments<F> {
+    /// 根据文件序号和扩展名生成路径
+    output: F,
+    split: SplitPolicy,
+    file: Option<File>,
+    /// 当前文件已写入的字节数
+    size: u64,
+    paths: Vec<PathBuf>,
+}
+
+impl<F: FnMut(usize, &str) -> PathBuf> Segments<F> {
+    fn is_open(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// 当前文件是否已达到分段条件
+    fn should_split(&self, duration: Duration) -> bool {
+        self.is_open() && self.split.should_split(duration, self.size)
+    }
+
+    async fn open(&mut self, extension: &str) -> Result<()> {
+        self.close().await?;
+        let path = (self.output)(self.paths.len(), extension);
+        self.file = Some(File::create(&path).await?);
+        self.paths.push(path);
+        self.size = 0;
+        Ok(())
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            file.write_all(data).await?;
+            self.size += data.len() as u64;
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// 每个FLV文件开头需要的Tag
+#[derive(Debug, Default)]
+struct FlvHeaders {
+    metadata: Option<FlvTag>,
+    video: Option<FlvTag>,
+    audio: Option<FlvTag>,
+}
+
+impl FlvHeaders {
+    /// 保存元数据或解码配置，其他Tag返回`false`
+    fn update(&mut self, tag: &FlvTag) -> bool {
+        let slot = if tag.is_script() {
+            &mut self.metadata
+        } else if tag.is_sequence_header() && tag.is_video() {
+            &mut self.video
+        } else if tag.is_sequence_header() {
+            &mut self.audio
+        } else {
+            return false;
+        };
+        *slot = Some(tag.clone());
+        true
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &FlvTag> {
+        self.metadata.iter().chain(&self.video).chain(&self.audio)
+    }
+}
+
+impl BilibiliLiveSource {
+    /// 录制直播间，直到下播或连续重连失败超过`RecordOptions::max_retries`次
+    ///
+    /// 断流后自动重连，每次连接都从新文件开始。`output`根据文件序号（从0开始）和扩展名生成路径。
+    /// 返回录制的全部文件，未录制到任何数据时返回错误
+    pub async fn record<F>(
+        &self,
+        room_id: u64,
+        options: &RecordOptions,
+        output: F,
+    ) -> Result<Vec<PathBuf>>
+    where
+        F: FnMut(usize, &str) -> PathBuf,
+    {
+        let room_id = self.0.request_room_init(room_id).await?.room_id;
+        let mut segments = Segments {
+            output,
+            split: options.split,
+            file: None,
+            size: 0,
+            paths: Vec::new(),
+        };
+        let mut retries = 0;
+        let mut last_error = None;
+        loop {
+            let result = self
+                .record_connection(room_id, options, &mut segments)
+                .await;
+            segments.close().await?;
+            match result {
+                Ok(None) => break,
+                // 收到数据后断流，重新计数
+                Ok(Some(true)) => retries = 0,
+                Ok(Some(false)) => retries += 1,
+                Err(error @ VideoSourceError::IoError(_)) => return Err(error),
+                Err(error) => {
+                    last_error = Some(error);
+                    retries += 1;
+                }
+            }
+            if retries > options.max_retries {
+                break;
+            }
+            tokio::time::sleep(options.retry_delay(retries)).await;
+        }
+        if segments.paths.is_empty() {
+            Err(last_error.unwrap_or_else(|| {
+                VideoSourceError::NoSuchResource(format!("直播间未开播: room_id={}", room_id))
+            }))
+        } else {
+            Ok(segments.paths)
+        }
+    }
+
+    /// 录制一次连接，下播时返回`None`，否则返回是否收到了数据
+    async fn record_connection<F>(
+        &self,
+        room_id: u64,
+        options: &RecordOptions,
+        segments: &mut Segments<F>,
+    ) -> Result<Option<bool>>
+    where
+        F: FnMut(usize, &str) -> PathBuf,
+    {
+        if self.0.request_room_init(room_id).await?.live_status != LIVE_STATUS_LIVE {
+            return Ok(None);
+        }
+        let stream = self
+            .live_stream(room_id, options.quality, options.protocol)
+            .await?;
+        match stream.protocol {
+            LiveProtocol::Flv => self.record_flv(&stream, options, segments).await,
+            LiveProtocol::Hls => self.record_hls(&stream, options, segments).await,
+        }
+        .map(Some)
+    }
+
+    /// 在关键帧处分段，每个文件以文件头、元数据和解码配置开始，时间戳从0开始
+    async fn record_flv<F>(
+        &self,
+        stream: &LiveStream,
+        options: &RecordOptions,
+        segments: &mut Segments<F>,
+    ) -> Result<bool>
+    where
+        F: FnMut(usize, &str) -> PathBuf,
+    {
+        let mut data = self.live_data(stream, options.stall_timeout);
+        let mut reader = FlvReader::default();
+        let mut headers = FlvHeaders::default();
+        let mut base = 0;
+        let mut received = false;
+        while let Some(chunk) = data.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                // 已收到数据后断流，由调用方重连
+                Err(_) if received => break,
+                Err(error) => return Err(error),
+            };
+            reader.push(&chunk);
+            while let Some(tag) = reader.next_tag()? {
+                received = true;
+                if headers.update(&tag) {
+                    // 解码配置变化时写入当前文件，元数据只在文件开头写入
+                    if !tag.is_script() {
+                        segments
+                            .write(&tag.encode(tag.timestamp.saturating_sub(base)))
+                            .await?;
+                    }
+                    continue;
+                }
+                // 没有视频时在任意音频处分段
+                let split_point = if headers.video.is_some() {
+                    tag.is_keyframe()
+                } else {
+                    tag.is_audio()
+                };
+                if !split_point && !segments.is_open() {
+                    continue;
+                }
+                let duration = Duration::from_millis(tag.timestamp.saturating_sub(base) as u64);
+                if split_point && (!segments.is_open() || segments.should_split(duration)) {
+                    segments.open(stream.extension()).await?;
+                    if let Some(header) = reader.header() {
+                        segments.write(header).await?;
+                    }
+                    for header in headers.iter() {
+                        segments.write(&header.encode(0)).await?;
+                    }
+                    base = tag.timestamp;
+                }
+                segments
+                    .write(&tag.encode(tag.timestamp.saturating_sub(base)))
+                    .await?;
+            }
+        }
+        Ok(received)
+    }
+
+    /// 在分片处分段，fMP4的每个文件以初始化分片开始
+    async fn record_hls<F>(
+        &self,
+        stream: &LiveStream,
+        options: &RecordOptions,
+        segments: &mut Segments<F>,
+    ) -> Result<bool>
+    where
+        F: FnMut(usize, &str) -> PathBuf,
+    {
+        let mut hls = self.0.hls_segments(&stream.urls, options.stall_timeout);
+        let mut init: Option<Bytes> = None;
+        let mut duration = Duration::ZERO;
+        let mut received = false;
+        while let Some(segment) = hls.next().await {
+            let segment = match segment {
+                Ok(segment) => segment,
+                Err(_) if received => break,
+                Err(error) => return Err(error),
+            };
+            received = true;
+            if segment.init {
+                // 初始化分片变化后的分片无法接在原文件之后
+                init = Some(segment.data);
+                segments.close().await?;
+                continue;
+            }
+            if !segments.is_open() || segments.should_split(duration) {
+                segments.open(stream.extension()).await?;
+                if let Some(init) = &init {
+                    segments.write(init).await?;
+                }
+                duration = Duration::ZERO;
+            }
+            segments.write(&segment.data).await?;
+            duration += Duration::from_secs_f64(segment.duration.max(0.0));
+        }
+        Ok(received)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::flv::sample;
+    use super::super::test::{live_server, mock_live_source, LiveServerState};
+    use super::{RecordOptions, SplitPolicy};
+    use crate::source::bilibili::LiveProtocol;
+    use std::path::{Path, PathBuf};
+    use std::sync::{atomic::Ordering, Arc};
+    use std::time::Duration;
+
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("youngoor-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn output(dir: &Path) -> impl FnMut(usize, &str) -> PathBuf + '_ {
+        move |index, extension| dir.join(format!("{:03}.{}", index, extension))
+    }
+
+    /// 文件中每个媒体Tag的时间戳
+    fn media_timestamps(path: &Path) -> Vec<u32> {
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(&data[..13], &sample::HEADER);
+        let tags = sample::read_tags(&data);
+        assert_eq!(
+            &tags[..3],
+            &[
+                sample::metadata(),
+                sample::video_header(),
+                sample::audio_header()
+            ]
+        );
+        tags[3..]
+            .iter()
+            .filter(|tag| tag.is_video())
+            .map(|tag| tag.timestamp)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn record_flv_test() {
+        let state = Arc::new(LiveServerState {
+            max_connections: 2,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state.clone()).await;
+        let source = mock_live_source(&server);
+        let dir = output_dir("record-flv");
+
+        let options = RecordOptions {
+            split: SplitPolicy {
+                max_duration: Some(Duration::from_secs(1)),
+                ..SplitPolicy::default()
+            },
+            retry_interval: Duration::ZERO,
+            ..RecordOptions::default()
+        };
+        let paths = source.record(6, &options, output(&dir)).await.unwrap();
+        // 两次连接，每次按1秒分为3段
+        assert_eq!(paths.len(), 6);
+        assert_eq!(paths[0], dir.join("000.flv"));
+        for path in &paths {
+            assert_eq!(media_timestamps(path), vec![0, 500]);
+        }
+        assert_eq!(state.connections.load(Ordering::SeqCst), 2);
+
+        // 文件大小超过200字节后在下一个关键帧处分段
+        let state = Arc::new(LiveServerState {
+            max_connections: 1,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state).await;
+        let source = mock_live_source(&server);
+        let options = RecordOptions {
+            split: SplitPolicy {
+                max_size: Some(200),
+                ..SplitPolicy::default()
+            },
+            ..options
+        };
+        let paths = source.record(5440, &options, output(&dir)).await.unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(media_timestamps(&paths[0]), vec![0, 500, 1000, 1500]);
+        assert_eq!(media_timestamps(&paths[1]), vec![0, 500]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn record_hls_test() {
+        let state = Arc::new(LiveServerState {
+            max_connections: 1,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state).await;
+        let source = mock_live_source(&server);
+        let dir = output_dir("record-hls");
+        let options = RecordOptions {
+            protocol: LiveProtocol::Hls,
+            split: SplitPolicy {
+                max_duration: Some(Duration::from_secs(2)),
+                ..SplitPolicy::default()
+            },
+            retry_interval: Duration::ZERO,
+            ..RecordOptions::default()
+        };
+        let paths = source.record(6, &options, output(&dir)).await.unwrap();
+        assert_eq!(paths, vec![dir.join("000.ts"), dir.join("001.ts")]);
+        assert_eq!(std::fs::read(&paths[0]).unwrap(), b"[10.ts][11.ts]");
+        assert_eq!(std::fs::read(&paths[1]).unwrap(), b"[12.ts][13.ts]");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn record_retry_test() {
+        let state = Arc::new(LiveServerState {
+            max_connections: 1,
+            broken: true,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state).await;
+        let source = mock_live_source(&server);
+        let dir = output_dir("record-retry");
+        let options = RecordOptions {
+            max_retries: 2,
+            retry_interval: Duration::ZERO,
+            ..RecordOptions::default()
+        };
+        assert!(source.record(6, &options, output(&dir)).await.is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn retry_delay_test() {
+        let options = RecordOptions::default();
+        let delays: Vec<_> = (0..8)
+            .map(|retries| options.retry_delay(retries).as_secs())
+            .collect();
+        assert_eq!(delays, vec![3, 3, 6, 12, 24, 48, 60, 60]);
+        let options = RecordOptions {
+            retry_interval: Duration::from_secs(90),
+            ..options
+        };
+        assert_eq!(options.retry_delay(3), Duration::from_secs(90));
+        let options = RecordOptions {
+            retry_interval: Duration::ZERO,
+            ..options
+        };
+        assert_eq!(options.retry_delay(100), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn record_stall_test() {
+        // 每次连接发送一半数据后停滞，超时后重连
+        let state = Arc::new(LiveServerState {
+            max_connections: 2,
+            stall: true,
+            ..LiveServerState::default()
+        });
+        let server = live_server(state.clone()).await;
+        let source = mock_live_source(&server);
+        let dir = output_dir("record-stall");
+        let options = RecordOptions {
+            retry_interval: Duration::ZERO,
+            stall_timeout: Duration::from_millis(300),
+            ..RecordOptions::default()
+        };
+        let paths = source.record(6, &options, output(&dir)).await.unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(state.connections.load(Ordering::SeqCst), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,372 @@
+//! 扫码登录
+
+use super::{BilibiliClient, BilibiliSource};
+use crate::cookie::{Cookie, CookieJar};
+use crate::error::VideoSourceError;
+use crate::secret::Secret;
+use crate::source::Result;
+
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use reqwest::header::SET_COOKIE;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+const REQUEST_QRCODE_URL: &str =
+    "https://passport.bilibili.com/x/passport-login/web/qrcode/generate";
+const POLL_QRCODE_URL: &str = "https://passport.bilibili.com/x/passport-login/web/qrcode/poll";
+/// 扫码状态：已确认
+const QRCODE_CONFIRMED: i32 = 0;
+/// 扫码状态：二维码已过期
+const QRCODE_EXPIRED: i32 = 86038;
+/// 扫码状态：已扫码，等待确认
+const QRCODE_SCANNED: i32 = 86090;
+/// 扫码状态：未扫码
+const QRCODE_NOT_SCANNED: i32 = 86101;
+/// 二维码的有效期，查询持续失败超过该时间时不再重试
+const QRCODE_LIFETIME: Duration = Duration::from_secs(180);
+
+impl BilibiliClient {
+    async fn request_login_qrcode(&self) -> Result<LoginQrCode> {
+        let url = self.api_url(REQUEST_QRCODE_URL)?;
+        let qrcode: QrCodeInfo = self
+            .bilibili_http_get_not_null(&url, std::iter::empty::<(&str, &str)>(), false)
+            .await?;
+        Ok(LoginQrCode {
+            url: qrcode.url,
+            key: qrcode.qrcode_key,
+        })
+    }
+
+    /// 查询扫码状态，确认登录时从响应的`Set-Cookie`中取得Cookie
+    async fn poll_login_qrcode(&self, key: &str) -> Result<LoginStatus> {
+        let url = self.api_url(POLL_QRCODE_URL)?;
+        let response = self
+            .bilibili_http_get(&url, [("qrcode_key", key)].iter(), false)
+            .await?;
+        let mut cookies = CookieJar::new();
+        response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| Cookie::from_set_cookie(value, &url))
+            .for_each(|cookie| cookies.insert(cookie));
+        let poll: QrCodePoll = Self::wrap_response_not_null(response).await?;
+        match poll.code {
+            QRCODE_CONFIRMED if cookies.is_empty() => Err(VideoSourceError::InvalidApiData(
+                "登录成功但没有返回Cookie".to_string(),
+            )),
+            QRCODE_CONFIRMED => Ok(LoginStatus::Confirmed(LoginCredential {
+                cookies,
+                refresh_token: Some(Secret::new(poll.refresh_token))
+                    .filter(|token| !token.expose().is_empty()),
+            })),
+            QRCODE_SCANNED => Ok(LoginStatus::Scanned),
+            QRCODE_NOT_SCANNED => Ok(LoginStatus::NotScanned),
+            QRCODE_EXPIRED => Ok(LoginStatus::Expired),
+            code => Err(VideoSourceError::RequestError(format!(
+                "{}: {}",
+                code, poll.message
+            ))),
+        }
+    }
+}
+
+impl BilibiliSource {
+    /// 生成登录二维码，用手机客户端扫描后通过`poll_login`查询状态
+    pub async fn login_qrcode(&self) -> Result<LoginQrCode> {
+        self.0.request_login_qrcode().await
+    }
+
+    /// 查询扫码状态，确认登录后通过`import_cookies`导入返回的Cookie
+    ///
+    /// 返回的Cookie缺少登录Cookie时返回错误
+    pub async fn poll_login(&mut self, qrcode: &LoginQrCode) -> Result<LoginStatus> {
+        let status = self.0.poll_login_qrcode(&qrcode.key).await?;
+        if let LoginStatus::Confirmed(credential) = &status {
+            let warnings = self.0.import_cookies(credential.cookies.clone());
+            if let Some(warning) = warnings.first() {
+                return Err(VideoSourceError::InvalidApiData(format!(
+                    "登录返回的Cookie无效: {}",
+                    warning
+                )));
+            }
+            self.0.refresh_token = credential.refresh_token.clone();
+        }
+        Ok(status)
+    }
+
+    /// 扫码登录返回的`refresh_token`
+    pub fn refresh_token(&self) -> Option<&str> {
+        self.0.refresh_token.as_ref().map(Secret::expose)
+    }
+
+    /// 每隔`interval`查询一次扫码状态，直到确认登录或二维码过期
+    ///
+    /// 状态变化时调用`on_status`，可用于提示用户已扫码。网络错误时继续查询，
+    /// 持续失败超过二维码有效期时返回错误
+    pub async fn wait_login<F>(
+        &mut self,
+        qrcode: &LoginQrCode,
+        interval: Duration,
+        mut on_status: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&LoginStatus),
+    {
+        let mut last = None;
+        let mut failing_since = None;
+        loop {
+            let status = match self.poll_login(qrcode).await {
+                Ok(status) => status,
+                Err(VideoSourceError::ReqwestError(error)) => {
+                    let since = *failing_since.get_or_insert_with(Instant::now);
+                    if since.elapsed() >= QRCODE_LIFETIME {
+                        return Err(error.into());
+                    }
+                    tokio::time::sleep(interval).await;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            failing_since = None;
+            if last.as_ref() != Some(&status) {
+                on_status(&status);
+            }
+            match status {
+                LoginStatus::Confirmed(_) => return Ok(()),
+                LoginStatus::Expired => return Err(VideoSourceError::QrCodeExpired),
+                LoginStatus::NotScanned | LoginStatus::Scanned => {}
+            }
+            last = Some(status);
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// 登录二维码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginQrCode {
+    /// 二维码内容
+    pub url: String,
+    /// 查询扫码状态所需的key
+    pub key: String,
+}
+
+impl LoginQrCode {
+    /// 以终端字符显示的二维码，每个字符表示上下两个模块，适合深色背景的终端
+    pub fn render_terminal(&self) -> Result<String> {
+        let code = QrCode::new(self.url.as_bytes()).map_err(|error| {
+            VideoSourceError::InvalidApiData(format!("二维码生成失败: {}", error))
+        })?;
+        Ok(code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build())
+    }
+}
+
+/// 扫码状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginStatus {
+    /// 未扫码
+    NotScanned,
+    /// 已扫码，等待在手机上确认
+    Scanned,
+    /// 二维码已过期，需要重新生成
+    Expired,
+    /// 已确认登录
+    Confirmed(LoginCredential),
+}
+
+/// 扫码登录返回的凭据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginCredential {
+    /// 带有域名、路径与过期时间的Cookie
+    pub cookies: CookieJar,
+    /// 用于在Cookie过期前刷新登录状态
+    pub refresh_token: Option<Secret>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QrCodeInfo {
+    pub url: String,
+    pub qrcode_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct QrCodePoll {
+    pub code: i32,
+    #[serde(default)]
+    pub message: String,
+    /// 确认登录后返回
+    #[serde(default)]
+    pub refresh_token: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::mock_source;
+    use super::LoginStatus;
+    use crate::error::VideoSourceError;
+    use crate::source::mock::{MockResponse, MockRoutes};
+    use crate::source::VideoSource;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    const QRCODE_URL: &str =
+        "https://account.bilibili.com/h5/account-h5/auth/scan-web?navhide=1&qrcode_key=key1";
+
+    /// 2100年
+    const FUTURE: i64 = 4102444800;
+
+    fn poll_response(code: i32, refresh_token: &str) -> MockResponse {
+        MockResponse::json(format!(
+            r#"{{"code":0,"message":"0","data":{{"url":"","refresh_token":"{}","timestamp":0,"code":{},"message":""}}}}"#,
+            refresh_token, code
+        ))
+    }
+
+    /// `key1`依次返回未扫码、已扫码、已确认，`key4`第一次查询时断开连接，`key2`已过期
+    fn passport_routes(polls: Arc<AtomicUsize>) -> MockRoutes {
+        MockRoutes::new()
+            .json(
+                "/x/passport-login/web/qrcode/generate",
+                format!(
+                    r#"{{"code":0,"message":"0","data":{{"url":"{}","qrcode_key":"key1"}}}}"#,
+                    QRCODE_URL
+                ),
+            )
+            .route(
+                "/x/passport-login/web/qrcode/poll",
+                move |request| match request.query("qrcode_key").as_deref() {
+                    Some(key @ ("key1" | "key4")) => match polls.fetch_add(1, Ordering::SeqCst) {
+                        0 if key == "key4" => MockResponse::disconnect(),
+                        0 => poll_response(86101, ""),
+                        1 => poll_response(86090, ""),
+                        _ => poll_response(0, "refresh")
+                            .with_header(
+                                "Set-Cookie",
+                                "SESSDATA=abc%2C123; Path=/; Domain=bilibili.com; \
+                                 Expires=Fri, 01 Jan 2100 00:00:00 GMT; HttpOnly; Secure",
+                            )
+                            .with_header(
+                                "Set-Cookie",
+                                "bili_jct=def; Path=/; Domain=bilibili.com; \
+                                 Expires=Fri, 01 Jan 2100 00:00:00 GMT",
+                            )
+                            .with_header(
+                                "Set-Cookie",
+                                "DedeUserID=42; Path=/; Domain=bilibili.com",
+                            ),
+                    },
+                    Some("key2") => poll_response(86038, ""),
+                    _ => MockResponse::json(r#"{"code":-400,"message":"请求错误"}"#),
+                },
+            )
+    }
+
+    #[tokio::test]
+    async fn login_test() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let server = passport_routes(polls.clone()).start().await;
+        let mut source = mock_source(&server);
+
+        let qrcode = source.login_qrcode().await.unwrap();
+        assert_eq!(
+            (qrcode.url.as_str(), qrcode.key.as_str()),
+            (QRCODE_URL, "key1")
+        );
+        assert_eq!(
+            source.poll_login(&qrcode).await.unwrap(),
+            LoginStatus::NotScanned
+        );
+        assert_eq!(
+            source.poll_login(&qrcode).await.unwrap(),
+            LoginStatus::Scanned
+        );
+        assert_eq!(source.token(), None);
+        let cookie = "SESSDATA=abc%2C123; bili_jct=def; DedeUserID=42";
+        let credential = match source.poll_login(&qrcode).await.unwrap() {
+            LoginStatus::Confirmed(credential) => credential,
+            status => panic!("{:?}", status),
+        };
+        assert_eq!(credential.cookies.to_header(), cookie);
+        assert_eq!(source.token(), Some(cookie));
+        assert_eq!(source.refresh_token(), Some("refresh"));
+        assert!(source.cookie_warnings().is_empty());
+        // 保留域名、路径与过期时间
+        let sessdata = credential.cookies.get("SESSDATA").unwrap();
+        assert_eq!(sessdata.domain, "bilibili.com");
+        assert!(sessdata.include_subdomains && sessdata.secure);
+        assert_eq!(sessdata.expires, Some(FUTURE));
+        assert_eq!(credential.cookies.get("DedeUserID").unwrap().expires, None);
+
+        // 状态变化时才通知
+        polls.store(0, Ordering::SeqCst);
+        let mut source = mock_source(&server);
+        let mut statuses = vec![];
+        source
+            .wait_login(&qrcode, Duration::from_millis(10), |status| {
+                statuses.push(status.clone())
+            })
+            .await
+            .unwrap();
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(source.token(), Some(cookie));
+
+        // 网络错误时继续查询
+        polls.store(0, Ordering::SeqCst);
+        let mut source = mock_source(&server);
+        let retried = super::LoginQrCode {
+            key: "key4".to_string(),
+            ..qrcode.clone()
+        };
+        let mut statuses = vec![];
+        source
+            .wait_login(&retried, Duration::from_millis(10), |status| {
+                statuses.push(status.clone())
+            })
+            .await
+            .unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0], LoginStatus::Scanned);
+        assert_eq!(source.token(), Some(cookie));
+
+        let expired = super::LoginQrCode {
+            key: "key2".to_string(),
+            ..qrcode.clone()
+        };
+        let result = source
+            .wait_login(&expired, Duration::from_millis(10), |_| {})
+            .await;
+        assert!(matches!(result, Err(VideoSourceError::QrCodeExpired)));
+        let invalid = super::LoginQrCode {
+            key: "key3".to_string(),
+            ..qrcode
+        };
+        let result = source.poll_login(&invalid).await;
+        assert!(matches!(result, Err(VideoSourceError::RequestError(_))));
+    }
+
+    #[test]
+    fn qrcode_render_test() {
+        let qrcode = super::LoginQrCode {
+            url: QRCODE_URL.to_string(),
+            key: "key1".to_string(),
+        };
+        let text = qrcode.render_terminal().unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        // 每行字符表示两行模块，四周留有空白
+        let width = lines[0].chars().count();
+        assert!(width > 21 + 8);
+        assert!(lines.iter().all(|line| line.chars().count() == width));
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(text.chars().all(|c| " ▀▄█\n".contains(c)));
+    }
+}
@@ -0,0 +1,2750 @@
+mod audio;
+pub mod avbv;
+mod collection;
+mod course;
+mod favorite;
+mod live;
+mod login;
+mod space;
+mod wbi;
+
+pub use live::{
+    BilibiliLiveSource, LiveProtocol, LiveRoom, LiveStream, RecordOptions, SplitPolicy,
+};
+pub use login::{LoginCredential, LoginQrCode, LoginStatus};
+pub use space::SpaceFilter;
+
+use super::{
+    AccountTier, AudioQuality, EntryKey, PartSelection, Quality, Resolution, ResolveOptions,
+    Result, StreamFormat, StreamKind, StreamPreference, VideoCodec, VideoEntry, VideoEntryStream,
+    VideoInfo, VideoMetadata, VideoSource, VideoType,
+};
+use crate::cookie::{CookieJar, CookieWarning};
+use crate::download::DownloadOptions;
+use crate::error::VideoSourceError;
+use crate::secret::Secret;
+
+use futures::future::BoxFuture;
+use reqwest::{
+    header::{HeaderValue, COOKIE, LOCATION, REFERER},
+    redirect::Policy,
+    RequestBuilder, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+const REQUEST_VIDEO_INFO_URL: &str = "https://api.bilibili.com/x/web-interface/view/detail";
+const REQUEST_VIDEO_URL: &str = "https://api.bilibili.com/x/player/playurl";
+const REQUEST_SSID_BY_MDID_URL: &str = "https://api.bilibili.com/pgc/review/user";
+const REQUEST_BANGUMI_INFO_URL: &str = "https://api.bilibili.com/pgc/view/web/season";
+
+/// Cookie所属的域名
+const COOKIE_DOMAIN: &str = "bilibili.com";
+/// 登录所需的Cookie
+const LOGIN_COOKIES: [&str; 2] = ["SESSDATA", "bili_jct"];
+/// 下载视频时的`Referer`
+const DOWNLOAD_REFERER: &str = "https://www.bilibili.com";
+/// 分享短链接的域名
+const SHORT_LINK_HOSTS: [&str; 4] = ["b23.tv", "bili2233.cn", "bili22.cn", "bili33.cn"];
+/// 解析短链接时最多跟随的跳转次数
+const MAX_REDIRECTS: usize = 5;
+/// `fnval`中请求杜比音频的位
+const FNVAL_DOLBY_AUDIO: u32 = 256;
+/// `fnval`中请求AV1编码的位
+const FNVAL_AV1: u32 = 2048;
+/// `fnval`中请求4K的位
+const FNVAL_4K: u32 = 128;
+/// `fnval`中请求HDR的位
+const FNVAL_HDR: u32 = 64;
+/// `fnval`中请求杜比视界的位
+const FNVAL_DOLBY_VISION: u32 = 512;
+/// `fnval`中请求8K的位
+const FNVAL_8K: u32 = 1024;
+
+#[derive(Clone, Debug, Default)]
+struct BilibiliClient {
+    client: reqwest::Client,
+    /// 不自动跟随跳转，用于解析短链接，首次使用时创建
+    redirect_client: OnceCell<reqwest::Client>,
+    cookies: CookieJar,
+    /// `set_token`设置或导入的Cookie，即`VideoSource::token`
+    token: Option<Secret>,
+    /// 扫码登录返回的`refresh_token`，用于刷新Cookie
+    refresh_token: Option<Secret>,
+    /// 测试时替换接口地址的协议、域名和端口，用于连接本地服务器
+    #[cfg(test)]
+    origin: Option<Url>,
+    resolve_options: ResolveOptions,
+}
+
+#[derive(Debug, Default)]
+pub struct BilibiliSource(BilibiliClient);
+
+#[derive(Debug, Eq, PartialEq)]
+enum UrlType {
+    /// BV ID，AV号链接会被转换为BV号
+    Video(String),
+    /// media id
+    Bangumi(u64),
+    /// season id
+    Season(u64),
+    /// episode id
+    Episode(u64),
+    /// 收藏夹id
+    Favorite(u64),
+    /// UP主投稿，筛选条件来自链接参数
+    Space { mid: u64, filter: SpaceFilter },
+    /// 合集
+    Collection { mid: u64, season_id: u64 },
+    /// 系列
+    Series { mid: u64, series_id: u64 },
+    /// 音频auid
+    Audio(u64),
+    /// 歌单amid
+    AudioMenu(u64),
+    /// 课程season id
+    CourseSeason(u64),
+    /// 课程episode id
+    CourseEpisode(u64),
+}
+
+/// 剧集链接中的ID
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum BangumiId {
+    /// media id
+    Media(u64),
+    /// season id
+    Season(u64),
+    /// episode id
+    Episode(u64),
+}
+
+impl VideoSource for BilibiliSource {
+    fn pretty_name(&self) -> &'static str {
+        "bilibili"
+    }
+
+    fn entries(&self, url: &Url, parts: &PartSelection) -> Result<VideoEntryStream<'_>> {
+        use async_stream::try_stream;
+
+        // 未指定分P时使用链接中的`?p=`
+        let parts = match Self::url_part(url) {
+            Some(part) if parts.is_all() => PartSelection::single(part),
+            _ => parts.clone(),
+        };
+        match Self::url_type(url) {
+            Some(UrlType::Video(bvid)) => Ok(Box::pin(try_stream! {
+                let detail = self.0.request_video_info(&bvid).await?;
+                for p_info in &detail.view.pages {
+                    if parts.contains(p_info.page as u32) {
+                        yield Self::video_entry(
+                            &bvid,
+                            p_info.cid,
+                            p_info.part.clone(),
+                            None,
+                            detail.metadata(p_info),
+                        );
+                    }
+                }
+            })),
+            Some(UrlType::Favorite(media_id)) => Ok(self.favorite_list(media_id, parts)),
+            Some(UrlType::Space { mid, filter }) => Ok(self.space_video_list(mid, filter, parts)),
+            Some(UrlType::Collection { mid, season_id }) => {
+                Ok(self.collection_list(mid, season_id, parts))
+            }
+            Some(UrlType::Series { mid, series_id }) => Ok(self.series_list(mid, series_id, parts)),
+            Some(UrlType::Audio(song_id)) => Ok(self.song(song_id)),
+            Some(UrlType::AudioMenu(menu_id)) => Ok(self.song_menu(menu_id, parts)),
+            Some(UrlType::CourseSeason(season_id)) => {
+                Ok(self.course_list(("season_id", season_id), None, parts))
+            }
+            Some(UrlType::CourseEpisode(episode_id)) => {
+                Ok(self.course_list(("ep_id", episode_id), Some(episode_id), parts))
+            }
+            Some(UrlType::Bangumi(media_id)) => {
+                Ok(self.bangumi_list(BangumiId::Media(media_id), parts))
+            }
+            Some(UrlType::Season(season_id)) => {
+                Ok(self.bangumi_list(BangumiId::Season(season_id), parts))
+            }
+            Some(UrlType::Episode(episode_id)) => {
+                Ok(self.bangumi_list(BangumiId::Episode(episode_id), parts))
+            }
+            None => Err(VideoSourceError::InvalidUrl(url.to_owned())),
+        }
+    }
+
+    fn expand_entry<'a>(
+        &'a self,
+        entry: &'a VideoEntry,
+    ) -> Option<BoxFuture<'a, Result<Vec<VideoEntry>>>> {
+        match entry.key.get()? {
+            EntryLocator::Video { bvid, cid: None } => {
+                Some(Box::pin(self.video_parts(bvid.clone(), entry)))
+            }
+            _ => None,
+        }
+    }
+
+    fn resolve_entry<'a>(
+        &'a self,
+        entry: &'a VideoEntry,
+        video_type: VideoType,
+        quality: &'a Quality,
+        preference: &'a StreamPreference,
+    ) -> BoxFuture<'a, Result<VideoInfo>> {
+        Box::pin(async move {
+            let dimension = DimensionCode::from(quality);
+            let video = entry.clone().into_video(video_type, quality, preference);
+            let locator: EntryLocator = entry
+                .key
+                .get()
+                .cloned()
+                .ok_or_else(|| VideoSourceError::InvalidEntry(entry.key.to_string()))?;
+            let urls = match locator {
+                EntryLocator::Video { bvid, cid } => {
+                    // 未展开的多P视频需先经`expand_entry`展开
+                    let cid = match cid {
+                        Some(cid) => cid,
+                        None => self.0.request_video_info(&bvid).await?.single_cid()?,
+                    };
+                    self.0
+                        .request_video_url(&bvid, cid, video_type.into(), dimension, preference)
+                        .await?
+                }
+                EntryLocator::Course {
+                    aid,
+                    cid,
+                    episode_id,
+                } => {
+                    self.0
+                        .request_course_video_url(
+                            (aid, cid, episode_id),
+                            &entry.title,
+                            video_type.into(),
+                            dimension,
+                            preference,
+                        )
+                        .await?
+                }
+                EntryLocator::Song { song_id } => {
+                    let audio = vec![self.0.request_song_url(song_id).await?];
+                    return Ok(VideoInfo {
+                        expires: url_deadline(&audio),
+                        audio,
+                        ..video
+                    });
+                }
+            };
+            Ok(urls.fill(video))
+        })
+    }
+
+    fn valid(&self, url: &Url) -> bool {
+        Self::url_type(url).is_some()
+    }
+
+    fn resolve<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<Url>> {
+        Box::pin(async move {
+            if Self::is_short_link(url) {
+                self.0.request_redirect_target(url).await
+            } else {
+                Ok(url.clone())
+            }
+        })
+    }
+
+    fn set_token(&mut self, token: String) {
+        self.0.set_token(token)
+    }
+
+    fn token(&self) -> Option<&str> {
+        self.0.token()
+    }
+
+    fn cookies(&self) -> Option<&CookieJar> {
+        Some(&self.0.cookies)
+    }
+
+    /// 只保留Bilibili的Cookie，按请求的域名发送
+    ///
+    /// 返回缺失或已过期的登录Cookie（`SESSDATA`、`bili_jct`），已过期的Cookie不会导入
+    fn import_cookies(&mut self, cookies: CookieJar) -> Vec<CookieWarning> {
+        self.0.import_cookies(cookies)
+    }
+
+    fn qualities(&self) -> Vec<Quality> {
+        DimensionCode::KNOWN
+            .iter()
+            .filter(|dimension| **dimension != DimensionCode::P360)
+            .map(|dimension| dimension.quality())
+            .collect()
+    }
+
+    fn resolve_options(&self) -> ResolveOptions {
+        self.0.resolve_options.clone()
+    }
+}
+
+impl BilibiliClient {
+    fn set_token(&mut self, token: String) {
+        self.cookies = CookieJar::from_header(&token, COOKIE_DOMAIN);
+        self.token = Some(token.into());
+    }
+
+    fn token(&self) -> Option<&str> {
+        self.token.as_ref().map(Secret::expose)
+    }
+
+    /// 只保留Bilibili的Cookie，返回缺失或过期的登录Cookie
+    fn import_cookies(&mut self, mut cookies: CookieJar) -> Vec<CookieWarning> {
+        cookies.retain_domain(COOKIE_DOMAIN);
+        let warnings = cookies.check(&LOGIN_COOKIES);
+        cookies.remove_expired();
+        self.token = Some(Secret::new(cookies.to_header())).filter(|token| !token.is_empty());
+        self.cookies = cookies;
+        warnings
+    }
+
+    fn has_cookie(&self) -> bool {
+        !self.cookies.is_empty()
+    }
+    /// 请求视频详情与标签
+    async fn request_video_info(&self, bvid: &str) -> Result<VideoDetail> {
+        let url = self.api_url(REQUEST_VIDEO_INFO_URL)?;
+        self.bilibili_http_get_not_null(&url, [("bvid", bvid)].iter(), self.has_cookie())
+            .await
+    }
+    /// 请求剧集ssid
+    async fn request_bangumi_ssid(&self, media_id: u64) -> Result<u64> {
+        let query_param = [("media_id", media_id.to_string())];
+        let url = self.api_url(REQUEST_SSID_BY_MDID_URL)?;
+        let result: BangumiInfo = self
+            .bilibili_http_get_not_null(&url, query_param.iter(), self.has_cookie())
+            .await?;
+        Ok(result.media.season_id)
+    }
+    async fn request_bangumi_info(&self, ssid: u64) -> Result<EpisodesInfo> {
+        self.request_season(("season_id", ssid.to_string())).await
+    }
+    /// 通过epid请求所在剧集
+    async fn request_bangumi_info_by_epid(&self, epid: u64) -> Result<EpisodesInfo> {
+        self.request_season(("ep_id", epid.to_string())).await
+    }
+    async fn request_season(&self, query_param: (&str, String)) -> Result<EpisodesInfo> {
+        let url = self.api_url(REQUEST_BANGUMI_INFO_URL)?;
+        self.bilibili_http_get_not_null(&url, [query_param].iter(), self.has_cookie())
+            .await
+    }
+    /// 请求剧集，ep链接请求所在的剧集
+    async fn request_bangumi_season(&self, id: BangumiId) -> Result<EpisodesInfo> {
+        match id {
+            BangumiId::Media(media_id) => {
+                let ssid = self.request_bangumi_ssid(media_id).await?;
+                self.request_bangumi_info(ssid).await
+            }
+            BangumiId::Season(ssid) => self.request_bangumi_info(ssid).await,
+            BangumiId::Episode(epid) => self.request_bangumi_info_by_epid(epid).await,
+        }
+    }
+    async fn request_video_url(
+        &self,
+        bvid: &str,
+        cid: u64,
+        vide_type: VideoTypeCode,
+        dimension: DimensionCode,
+        preference: &StreamPreference,
+    ) -> Result<PlayUrl> {
+        let query_params: HashMap<_, _> = VideoUrlRequest {
+            bvid: bvid.to_string(),
+            cid,
+            fnver: 0,
+            fnval: vide_type.fnval(dimension, preference),
+            qn: dimension,
+            fourk: 1,
+        }
+        .into();
+        let url = self.api_url(REQUEST_VIDEO_URL)?;
+        let result: VideoUrlInfo = self
+            .bilibili_http_get_not_null(&url, query_params.iter(), dimension.need_login())
+            .await?;
+        result.into_urls(dimension, preference, &format!("bvid={}", bvid))
+    }
+
+    fn api_url(&self, url: &str) -> Result<Url> {
+        BilibiliSource::parse_url(url)
+    }
+
+    /// 实际请求的地址
+    #[cfg(not(test))]
+    fn request_url(&self, url: &Url) -> Url {
+        url.clone()
+    }
+
+    /// 设置了`origin`时替换地址的协议、域名和端口
+    #[cfg(test)]
+    fn request_url(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        if let Some(origin) = &self.origin {
+            let _ = url.set_scheme(origin.scheme());
+            let _ = url.set_host(origin.host_str());
+            let _ = url.set_port(origin.port());
+        }
+        url
+    }
+
+    async fn redirect_client(&self) -> Result<&reqwest::Client> {
+        let client = self
+            .redirect_client
+            .get_or_try_init(|| async {
+                reqwest::Client::builder().redirect(Policy::none()).build()
+            })
+            .await?;
+        Ok(client)
+    }
+
+    /// 逐次跟随跳转，直到得到可识别的链接
+    async fn request_redirect_target(&self, url: &Url) -> Result<Url> {
+        let mut url = url.clone();
+        for _ in 0..MAX_REDIRECTS {
+            if BilibiliSource::url_type(&url).is_some() {
+                return Ok(url);
+            }
+            let response = self
+                .redirect_client()
+                .await?
+                .get(url.clone())
+                .send()
+                .await?;
+            if !response.status().is_redirection() {
+                return Err(VideoSourceError::InvalidUrl(url));
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| VideoSourceError::InvalidUrl(url.clone()))?;
+            url = url
+                .join(location)
+                .map_err(|_| VideoSourceError::InvalidUrl(url.clone()))?;
+        }
+        if BilibiliSource::url_type(&url).is_some() {
+            Ok(url)
+        } else {
+            Err(VideoSourceError::InvalidUrl(url))
+        }
+    }
+
+    async fn bilibili_http_get_not_null<T, I, K, V>(
+        &self,
+        url: &Url,
+        params: I,
+        with_cookie: bool,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+        I: Iterator,
+        I::Item: Borrow<(K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let response = self.bilibili_http_get(url, params, with_cookie).await?;
+        Self::wrap_response_not_null(response).await
+    }
+
+    async fn bilibili_http_get<I, K, V>(
+        &self,
+        url: &Url,
+        params: I,
+        with_cookie: bool,
+    ) -> Result<reqwest::Response>
+    where
+        I: Iterator,
+        I::Item: Borrow<(K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut url = url.clone();
+        url.query_pairs_mut().extend_pairs(params);
+        let mut request = self.client.get(self.request_url(&url));
+        request = self.wrap_cookie(request, &url, with_cookie)?;
+        Self::http_request(request).await
+    }
+    async fn http_request(request: RequestBuilder) -> Result<reqwest::Response> {
+        let response = request.send().await?;
+        if response.status() != StatusCode::OK {
+            let reason = response
+                .status()
+                .canonical_reason()
+                .unwrap_or("请求错误")
+                .to_string();
+            return Err(VideoSourceError::RequestError(reason));
+        }
+        Ok(response)
+    }
+    /// 附加请求`url`时对应的Cookie
+    fn wrap_cookie(
+        &self,
+        request: RequestBuilder,
+        url: &Url,
+        with_cookie: bool,
+    ) -> Result<RequestBuilder> {
+        if with_cookie {
+            if let Some(cookie) = self.cookies.header_for(url) {
+                Ok(request.header(COOKIE, cookie))
+            } else {
+                Err(VideoSourceError::NeedLogin)
+            }
+        } else {
+            Ok(request)
+        }
+    }
+    async fn wrap_response_null<T: DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<Option<T>> {
+        let url = response.url().to_string();
+        let result: Response<T> = response.json().await?;
+        // assert!(result.data.is_some());
+        match result.code {
+            0 => Ok(result.data),
+            -400 => Err(VideoSourceError::RequestError(result.message())),
+            -404 => Err(VideoSourceError::NoSuchResource(url)),
+            _ => Err(VideoSourceError::RequestError(result.message())),
+        }
+    }
+    async fn wrap_response_not_null<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+        let url = response.url().clone().to_string();
+        let result = Self::wrap_response_null(response).await?;
+        //assert!(result.is_some());
+        result.ok_or(VideoSourceError::NoSuchResource(url))
+    }
+}
+impl BilibiliSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前Cookie中缺失或已过期的登录Cookie
+    pub fn cookie_warnings(&self) -> Vec<CookieWarning> {
+        self.0.cookies.check(&LOGIN_COOKIES)
+    }
+
+    /// 设置获取音视频流的并发数与请求间隔
+    pub fn set_resolve_options(&mut self, options: ResolveOptions) {
+        self.0.resolve_options = options;
+    }
+
+    /// 下载视频地址所需的选项，CDN要求带有`Referer`
+    pub fn download_options() -> DownloadOptions {
+        let mut options = DownloadOptions::default();
+        options
+            .headers
+            .insert(REFERER, HeaderValue::from_static(DOWNLOAD_REFERER));
+        options
+    }
+
+    /// 剧集中的分集，ep链接只返回对应的单集
+    fn bangumi_list(&self, id: BangumiId, parts: PartSelection) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let season = match self.0.request_bangumi_season(id).await {
+                Ok(season) => season,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            let episode_id = match id {
+                BangumiId::Episode(epid) => Some(epid),
+                BangumiId::Media(_) | BangumiId::Season(_) => None,
+            };
+            if let Some(epid) = episode_id {
+                if !season.episodes.iter().any(|episode| episode.id == epid) {
+                    yield Err(VideoSourceError::NoSuchResource(format!("epid={}", epid)));
+                    return;
+                }
+            }
+            for (episode, index) in season.episodes.iter().zip(1..) {
+                let selected = match episode_id {
+                    Some(epid) => episode.id == epid,
+                    None => parts.contains(index),
+                };
+                if !selected {
+                    continue;
+                }
+                let title = format!("{} {}", episode.title, episode.long_title);
+                let metadata = season.metadata(episode, index);
+                let pic = match Url::parse(&episode.cover) {
+                    Ok(pic) => pic,
+                    Err(_) => {
+                        let error = VideoSourceError::InvalidApiData(format!(
+                            "封面地址错误: {}",
+                            episode.cover
+                        ));
+                        yield Err(error.entry(metadata.id.unwrap_or_default(), title));
+                        continue;
+                    }
+                };
+                yield Ok(Self::video_entry(
+                    &episode.bvid,
+                    episode.cid,
+                    title,
+                    Some(pic),
+                    metadata,
+                ));
+            }
+        })
+    }
+
+    /// 列表中的视频，只包含列表提供的信息，获取音视频流前由`expand_entry`展开为各分P
+    fn list_video(
+        bvid: String,
+        title: String,
+        pic: Option<Url>,
+        metadata: VideoMetadata,
+    ) -> VideoEntry {
+        VideoEntry {
+            pic,
+            title,
+            lyric: None,
+            metadata: VideoMetadata {
+                id: Some(bvid.clone()),
+                ..metadata
+            },
+            key: EntryKey::new(EntryLocator::Video { bvid, cid: None }),
+        }
+    }
+
+    /// 请求列表中视频的全部分P，多P视频的标题为`视频标题 分P标题`
+    ///
+    /// 列表提供的合集、序号优先于视频详情中的信息
+    async fn video_parts(&self, bvid: String, entry: &VideoEntry) -> Result<Vec<VideoEntry>> {
+        let detail = self.0.request_video_info(&bvid).await?;
+        let multi_part = detail.view.pages.len() > 1;
+        Ok(detail
+            .view
+            .pages
+            .iter()
+            .map(|p_info| {
+                let title = if multi_part {
+                    format!("{} {}", entry.title, p_info.part)
+                } else {
+                    entry.title.clone()
+                };
+                let metadata = detail.metadata(p_info);
+                let metadata = VideoMetadata {
+                    series: entry.metadata.series.clone().or(metadata.series),
+                    series_url: entry.metadata.series_url.clone().or(metadata.series_url),
+                    episode: entry.metadata.episode.or(metadata.episode),
+                    ..metadata
+                };
+                Self::video_entry(&bvid, p_info.cid, title, entry.pic.clone(), metadata)
+            })
+            .collect())
+    }
+
+    fn video_entry(
+        bvid: &str,
+        cid: u64,
+        title: String,
+        pic: Option<Url>,
+        metadata: VideoMetadata,
+    ) -> VideoEntry {
+        VideoEntry {
+            pic,
+            title,
+            lyric: None,
+            metadata,
+            key: EntryKey::new(EntryLocator::Video {
+                bvid: bvid.to_string(),
+                cid: Some(cid),
+            }),
+        }
+    }
+
+    fn parse_url(url: &str) -> Result<Url> {
+        Url::parse(url).map_err(|_| VideoSourceError::RequestError(format!("无效的地址: {}", url)))
+    }
+
+    /// 链接中`?p=`指定的分P
+    fn url_part(url: &Url) -> Option<u32> {
+        url.query_pairs()
+            .find(|(key, _)| key == "p")
+            .and_then(|(_, part)| part.parse().ok())
+            .filter(|&part| part > 0)
+    }
+
+    fn is_short_link(url: &Url) -> bool {
+        url.host_str().is_some_and(|host| {
+            SHORT_LINK_HOSTS
+                .iter()
+                .any(|short_host| host.eq_ignore_ascii_case(short_host))
+        })
+    }
+
+    fn url_type(url: &Url) -> Option<UrlType> {
+        match url.host_str()?.to_ascii_lowercase().as_str() {
+            "www.bilibili.com" | "m.bilibili.com" | "bilibili.com" => Self::main_url_type(url),
+            "space.bilibili.com" => Self::space_url_type(url),
+            _ => None,
+        }
+    }
+
+    /// `space.bilibili.com`下的链接
+    fn space_url_type(url: &Url) -> Option<UrlType> {
+        let mut path = url.path_segments()?;
+        let mid: u64 = path.next()?.parse().ok()?;
+        let query = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        match path.next() {
+            None | Some("") | Some("video") | Some("upload") | Some("search") => {
+                Some(UrlType::Space {
+                    mid,
+                    filter: SpaceFilter::from_url(url),
+                })
+            }
+            Some("channel") => {
+                let id = query("sid")?.parse().ok()?;
+                match path.next() {
+                    Some("collectiondetail") => Some(UrlType::Collection { mid, season_id: id }),
+                    Some("seriesdetail") => Some(UrlType::Series { mid, series_id: id }),
+                    _ => None,
+                }
+            }
+            Some("lists") => {
+                let id = path.next()?.parse().ok()?;
+                match query("type").as_deref() {
+                    None | Some("season") => Some(UrlType::Collection { mid, season_id: id }),
+                    Some("series") => Some(UrlType::Series { mid, series_id: id }),
+                    _ => None,
+                }
+            }
+            // `ftype=collect`为收藏的合集，`fid`是合集ID而不是收藏夹ID，合集所属的UP主未知
+            Some("favlist") => match query("ftype").as_deref() {
+                None | Some("create") => query("fid")?.parse().ok().map(UrlType::Favorite),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// `www.bilibili.com`下的链接
+    fn main_url_type(url: &Url) -> Option<UrlType> {
+        let mut path = url.path_segments()?;
+        match path.next() {
+            Some("video") => {
+                let id = path.next()?;
+                if id.starts_with("BV") {
+                    Some(UrlType::Video(id.to_string()))
+                } else {
+                    avbv::parse_aid(id)
+                        .and_then(avbv::av_to_bv)
+                        .map(UrlType::Video)
+                }
+            }
+
+            Some("bangumi") => match path.next() {
+                Some("media") => {
+                    let media_id = path.next()?;
+                    if media_id.starts_with("md") {
+                        let id: std::result::Result<u64, _> =
+                            media_id.strip_prefix("md").unwrap_or("no id").parse();
+                        id.ok().map(UrlType::Bangumi)
+                    } else {
+                        None
+                    }
+                }
+                Some("play") => {
+                    let id = path.next()?;
+                    if let Some(ssid) = id.strip_prefix("ss") {
+                        ssid.parse().ok().map(UrlType::Season)
+                    } else if let Some(epid) = id.strip_prefix("ep") {
+                        epid.parse().ok().map(UrlType::Episode)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            Some("cheese") => match path.next() {
+                Some("play") => {
+                    let id = path.next()?;
+                    if let Some(season_id) = id.strip_prefix("ss") {
+                        season_id.parse().ok().map(UrlType::CourseSeason)
+                    } else if let Some(episode_id) = id.strip_prefix("ep") {
+                        episode_id.parse().ok().map(UrlType::CourseEpisode)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            Some("medialist") => match path.next() {
+                Some("detail") | Some("play") => Self::parse_medialist_id(path.next()?),
+                _ => None,
+            },
+            Some("list") => Self::parse_medialist_id(path.next()?),
+            Some("audio") => {
+                let id = path.next()?;
+                if let Some(song_id) = id.strip_prefix("au") {
+                    song_id.parse().ok().map(UrlType::Audio)
+                } else if let Some(menu_id) = id.strip_prefix("am") {
+                    menu_id.parse().ok().map(UrlType::AudioMenu)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// 解析`ml<收藏夹id>`
+    fn parse_medialist_id(id: &str) -> Option<UrlType> {
+        id.strip_prefix("ml")?.parse().ok().map(UrlType::Favorite)
+    }
+}
+
+/// Bilibili响应格式
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    pub code: i32,
+    #[serde(default)]
+    pub message: String,
+    /// 音频、直播等接口的错误信息，直播接口同时返回`message`
+    #[serde(default)]
+    pub msg: String,
+    // ttl: i32,
+    #[serde(alias = "data", alias = "result")]
+    pub data: Option<T>,
+}
+
+impl<T> Response<T> {
+    fn message(&self) -> String {
+        if self.message.is_empty() {
+            self.msg.clone()
+        } else {
+            self.message.clone()
+        }
+    }
+}
+
+/// 视频详情与标签
+#[derive(Debug, Deserialize)]
+struct VideoDetail {
+    #[serde(rename(deserialize = "View"))]
+    pub view: View,
+    /// 没有标签时为`null`
+    #[serde(rename(deserialize = "Tags"))]
+    pub tags: Option<Vec<VideoTag>>,
+}
+
+impl VideoDetail {
+    fn metadata(&self, p_info: &PInfo) -> VideoMetadata {
+        VideoMetadata {
+            tags: self
+                .tags
+                .iter()
+                .flatten()
+                .map(|tag| tag.tag_name.clone())
+                .collect(),
+            ..self.view.metadata(p_info)
+        }
+    }
+
+    /// 只有一个分P时的cid，多P视频返回`InvalidEntry`
+    fn single_cid(&self) -> Result<u64> {
+        match &self.view.pages[..] {
+            [p_info] => Ok(p_info.cid),
+            [] => Err(VideoSourceError::NoSuchResource(format!(
+                "没有分P: {}",
+                self.view.bvid
+            ))),
+            _ => Err(VideoSourceError::InvalidEntry(format!(
+                "多P视频需要先展开: {}",
+                self.view.bvid
+            ))),
+        }
+    }
+}
+
+/// 视频详情
+#[derive(Debug, Deserialize)]
+struct View {
+    pub bvid: String,
+    /// 简介
+    pub desc: String,
+    /// 发布时间（Unix时间戳，秒）
+    pub pubdate: i64,
+    /// UP主
+    pub owner: Owner,
+    pub pages: Vec<PInfo>,
+    /// 所属合集
+    pub ugc_season: Option<collection::UgcSeason>,
+}
+
+impl View {
+    fn metadata(&self, p_info: &PInfo) -> VideoMetadata {
+        VideoMetadata {
+            id: Some(self.bvid.clone()),
+            part: Some(p_info.page as u32),
+            duration: Some(Duration::from_secs(p_info.duration.max(0) as u64)),
+            resolution: p_info.dimension.resolution(),
+            uploader: Some(self.owner.name.clone()),
+            published: Some(self.pubdate),
+            description: Some(self.desc.clone()).filter(|desc| !desc.is_empty()),
+            series_url: self
+                .ugc_season
+                .as_ref()
+                .and_then(collection::UgcSeason::url),
+            ..VideoMetadata::default()
+        }
+    }
+}
+
+/// UP主
+#[derive(Debug, Deserialize)]
+struct Owner {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VideoTag {
+    pub tag_name: String,
+}
+
+/// Bilibili分P
+#[derive(Debug, Deserialize)]
+struct PInfo {
+    pub cid: u64,
+    /// 当前P
+    pub page: i32,
+    /// 视频标题
+    pub part: String,
+    /// 时间
+    pub duration: i32,
+    /// 分辨率
+    pub dimension: Dimension,
+}
+
+/// 视频分辨率
+#[derive(Debug, Deserialize)]
+struct Dimension {
+    pub width: i32,
+    pub height: i32,
+    /// - 0 :正常
+    /// - 1 :宽高对换
+    pub rotate: u8,
+}
+
+impl Dimension {
+    /// 宽高未知时返回`None`
+    fn resolution(&self) -> Option<Resolution> {
+        let (width, height) = if self.rotate == 1 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        if width > 0 && height > 0 {
+            Some(Resolution {
+                width: width as u32,
+                height: height as u32,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// 获取下载地址时的分辨率
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum DimensionCode {
+    P240,
+    P360,
+    P480,
+    P720,
+    P720F60,
+    P1080,
+    P1080P,
+    P1080F60,
+    P4K,
+    /// HDR真彩色
+    Hdr,
+    /// 杜比视界
+    DolbyVision,
+    P8K,
+    /// 未知的清晰度代码，保留原值
+    Unknown(i32),
+}
+
+impl DimensionCode {
+    /// 已知的清晰度，从低到高排列
+    pub const KNOWN: [DimensionCode; 12] = [
+        DimensionCode::P240,
+        DimensionCode::P360,
+        DimensionCode::P480,
+        DimensionCode::P720,
+        DimensionCode::P720F60,
+        DimensionCode::P1080,
+        DimensionCode::P1080P,
+        DimensionCode::P1080F60,
+        DimensionCode::P4K,
+        DimensionCode::Hdr,
+        DimensionCode::DolbyVision,
+        DimensionCode::P8K,
+    ];
+
+    pub fn need_login(&self) -> bool {
+        self.quality().tier > AccountTier::Guest
+    }
+
+    /// 对应的清晰度描述，未知代码的高度为0
+    pub fn quality(self) -> Quality {
+        let (height, label, tier) = match self {
+            DimensionCode::P240 => (240, "240P 极速", AccountTier::Guest),
+            DimensionCode::P360 => (360, "360P 流畅", AccountTier::Guest),
+            DimensionCode::P480 => (480, "480P 清晰", AccountTier::Guest),
+            DimensionCode::P720 => (720, "720P 高清", AccountTier::Member),
+            DimensionCode::P720F60 => (720, "720P60 高清", AccountTier::Premium),
+            DimensionCode::P1080 => (1080, "1080P 高清", AccountTier::Member),
+            DimensionCode::P1080P => (1080, "1080P+ 高清", AccountTier::Premium),
+            DimensionCode::P1080F60 => (1080, "1080P60 高清", AccountTier::Premium),
+            DimensionCode::P4K => (2160, "4K 超清", AccountTier::Premium),
+            DimensionCode::Hdr => (2160, "HDR 真彩", AccountTier::Premium),
+            DimensionCode::DolbyVision => (2160, "杜比视界", AccountTier::Premium),
+            DimensionCode::P8K => (4320, "8K 超高清", AccountTier::Premium),
+            DimensionCode::Unknown(code) => {
+                let tier = if code > i32::from(DimensionCode::P480) {
+                    AccountTier::Member
+                } else {
+                    AccountTier::Guest
+                };
+                return Quality {
+                    tier,
+                    ..Quality::new(0, &format!("未知清晰度（{}）", code))
+                };
+            }
+        };
+        Quality {
+            frame_rate: match self {
+                DimensionCode::P720F60 | DimensionCode::P1080F60 => 60,
+                _ => 30,
+            },
+            high_bitrate: self == DimensionCode::P1080P,
+            // 杜比视界也是HDR，排在HDR真彩色之上
+            hdr: matches!(self, DimensionCode::Hdr | DimensionCode::DolbyVision),
+            dolby: self == DimensionCode::DolbyVision,
+            tier,
+            ..Quality::new(height, label)
+        }
+    }
+}
+
+impl Display for DimensionCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let quality = self.quality();
+        match quality.tier {
+            AccountTier::Guest => write!(f, "{}", quality),
+            AccountTier::Member => write!(f, "{}（登录）", quality),
+            AccountTier::Premium => write!(f, "{}（大会员）", quality),
+        }
+    }
+}
+
+impl From<&Quality> for DimensionCode {
+    /// 不高于`quality`的最高清晰度，`quality`不要求HDR或杜比视界时不选择它们
+    fn from(quality: &Quality) -> Self {
+        quality
+            .code_in(
+                Self::KNOWN
+                    .iter()
+                    .map(|code| (*code, code.quality()))
+                    .filter(|(_, known)| {
+                        (!known.hdr || quality.hdr || quality.dolby)
+                            && (!known.dolby || quality.dolby)
+                    }),
+            )
+            .unwrap_or(DimensionCode::P240)
+    }
+}
+
+impl From<i32> for DimensionCode {
+    fn from(dimension: i32) -> Self {
+        match dimension {
+            6 => Self::P240,
+            16 => Self::P360,
+            32 => Self::P480,
+            64 => Self::P720,
+            74 => Self::P720F60,
+            80 => Self::P1080,
+            112 => Self::P1080P,
+            116 => Self::P1080F60,
+            120 => Self::P4K,
+            125 => Self::Hdr,
+            126 => Self::DolbyVision,
+            127 => Self::P8K,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<DimensionCode> for i32 {
+    fn from(dimension: DimensionCode) -> Self {
+        match dimension {
+            DimensionCode::P240 => 6,
+            DimensionCode::P360 => 16,
+            DimensionCode::P480 => 32,
+            DimensionCode::P720 => 64,
+            DimensionCode::P720F60 => 74,
+            DimensionCode::P1080 => 80,
+            DimensionCode::P1080P => 112,
+            DimensionCode::P1080F60 => 116,
+            DimensionCode::P4K => 120,
+            DimensionCode::Hdr => 125,
+            DimensionCode::DolbyVision => 126,
+            DimensionCode::P8K => 127,
+            DimensionCode::Unknown(code) => code,
+        }
+    }
+}
+
+/// 获取下载地址时的格式
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum VideoTypeCode {
+    Flv1 = 0,
+    Mp4 = 1,
+    Flv2 = 2,
+    Dash = 16,
+}
+
+impl Display for VideoTypeCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VideoTypeCode::Flv1 => f.write_str("FLV"),
+            VideoTypeCode::Mp4 => f.write_str("MP4"),
+            VideoTypeCode::Flv2 => f.write_str("FLV"),
+            VideoTypeCode::Dash => f.write_str("高清MP4"),
+        }
+    }
+}
+
+impl VideoTypeCode {
+    /// 请求参数`fnval`，DASH按清晰度附加4K、HDR、杜比视界与8K的功能位，按偏好附加AV1编码与
+    /// 杜比音频的功能位。只在请求HDR或杜比视界时附加对应的位，以免回退到HDR视频
+    fn fnval(self, dimension: DimensionCode, preference: &StreamPreference) -> u32 {
+        let mut fnval = self as u32;
+        if self == Self::Dash {
+            let requested = dimension.quality();
+            if requested.height >= 2160 {
+                fnval |= FNVAL_4K;
+            }
+            if requested.hdr {
+                fnval |= FNVAL_HDR;
+            }
+            if requested.dolby {
+                fnval |= FNVAL_DOLBY_VISION;
+            }
+            if requested.height >= 4320 {
+                fnval |= FNVAL_8K;
+            }
+            if preference.codecs.contains(&VideoCodec::Av1) {
+                fnval |= FNVAL_AV1;
+            }
+            if preference.audio >= AudioQuality::Dolby {
+                fnval |= FNVAL_DOLBY_AUDIO;
+            }
+        }
+        fnval
+    }
+}
+
+impl From<VideoTypeCode> for VideoType {
+    fn from(video_type: VideoTypeCode) -> Self {
+        match video_type {
+            VideoTypeCode::Flv1 => Self::Flv,
+            VideoTypeCode::Mp4 => Self::MP4,
+            VideoTypeCode::Flv2 => Self::Flv,
+            VideoTypeCode::Dash => Self::MP4,
+        }
+    }
+}
+impl From<VideoType> for VideoTypeCode {
+    fn from(video_type: VideoType) -> Self {
+        match video_type {
+            VideoType::Flv => Self::Flv2,
+            VideoType::MP4 => Self::Dash,
+        }
+    }
+}
+
+/// 视频下载请求
+#[derive(Debug)]
+struct VideoUrlRequest {
+    pub bvid: String,
+    pub cid: u64,
+    /// 分辨率
+    pub qn: DimensionCode,
+    /// 格式与附加功能，见`VideoTypeCode::fnval`
+    pub fnval: u32,
+    /// 固定为0
+    pub fnver: i32,
+    /// 是否允许4K
+    pub fourk: i32,
+}
+
+impl From<VideoUrlRequest> for HashMap<&'static str, String> {
+    fn from(data: VideoUrlRequest) -> Self {
+        let mut map = HashMap::with_capacity(6);
+        map.insert("bvid", data.bvid);
+        map.insert("cid", data.cid.to_string());
+        map.insert("qn", i32::from(data.qn).to_string());
+        map.insert("fnval", data.fnval.to_string());
+        map.insert("fnver", data.fnver.to_string());
+        map.insert("fourk", data.fourk.to_string());
+        map
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VideoUrlInfo {
+    /// 分辨率
+    pub quality: i32,
+    /// 视频分段
+    pub durl: Option<Vec<Durl>>,
+    /// dash音视频流信息
+    pub dash: Option<Dash>,
+}
+
+impl VideoUrlInfo {
+    /// `resource`用于错误信息
+    fn into_urls(
+        self,
+        dimension: DimensionCode,
+        preference: &StreamPreference,
+        resource: &str,
+    ) -> Result<PlayUrl> {
+        let requested = i32::from(dimension);
+        if let Some(flv) = self.durl {
+            // 接口已按权限降级，只检查实际清晰度是否符合回退方式
+            let quality = preference
+                .fallback
+                .select(requested, Some(self.quality))
+                .ok_or_else(|| VideoSourceError::NoSuchResource(resource.to_string()))?;
+            let mut backup_urls = HashMap::new();
+            let mut video = Vec::with_capacity(flv.len());
+            for durl in flv {
+                let url = BilibiliSource::parse_url(&durl.url)?;
+                backup_urls.insert(url.clone(), Self::backup_urls(&durl.backup_url));
+                video.push(url);
+            }
+            return Ok(PlayUrl {
+                video,
+                audio: vec![],
+                formats: vec![],
+                quality: DimensionCode::from(quality).quality(),
+                audio_quality: None,
+                backup_urls,
+            });
+        }
+        if let Some(dash) = self.dash {
+            let video: Result<Vec<_>> = dash
+                .video
+                .iter()
+                .map(|item| item.format(StreamKind::Video))
+                .collect();
+            let audio: Result<Vec<_>> = dash
+                .audio
+                .iter()
+                .chain(
+                    dash.dolby
+                        .iter()
+                        .flat_map(|dolby| dolby.audio.iter().flatten()),
+                )
+                .chain(dash.flac.iter().flat_map(|flac| &flac.audio))
+                .map(|item| item.format(StreamKind::Audio))
+                .collect();
+            let (video, audio) = (video?, audio?);
+            // 只在有可接受编码的清晰度中回退
+            let available = video
+                .iter()
+                .filter(|format| preference.select_video(Some(*format)).is_some())
+                .map(|format| format.id);
+            let quality = preference
+                .fallback
+                .select(requested, available)
+                .ok_or_else(|| VideoSourceError::NoSuchResource(resource.to_string()))?;
+            // 同一清晰度可能有多种编码
+            let video_format = preference
+                .select_video(video.iter().filter(|video| video.id == quality))
+                .ok_or_else(|| VideoSourceError::NoSuchResource(resource.to_string()))?;
+            let audio_format = preference
+                .select_audio(&audio)
+                .ok_or_else(|| VideoSourceError::NoSuchResource(resource.to_string()))?;
+            let backup_urls = [video_format, audio_format]
+                .iter()
+                .map(|format| (format.url.clone(), format.backup_urls.clone()))
+                .collect();
+            return Ok(PlayUrl {
+                video: vec![video_format.url.clone()],
+                audio: vec![audio_format.url.clone()],
+                quality: DimensionCode::from(quality).quality(),
+                audio_quality: audio_format.audio_quality,
+                formats: video.iter().chain(&audio).cloned().collect(),
+                backup_urls,
+            });
+        }
+        Err(VideoSourceError::NoSuchResource(resource.to_string()))
+    }
+
+    /// 忽略无效的备用地址
+    fn backup_urls(urls: &[String]) -> Vec<Url> {
+        urls.iter().filter_map(|url| Url::parse(url).ok()).collect()
+    }
+}
+
+/// 条目的定位信息，保存在`VideoEntry::key`中
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum EntryLocator {
+    /// 视频的分P，列表中尚未展开的视频`cid`为`None`
+    Video {
+        bvid: String,
+        cid: Option<u64>,
+    },
+    Course {
+        aid: u64,
+        cid: u64,
+        episode_id: u64,
+    },
+    Song {
+        song_id: u64,
+    },
+}
+
+/// 下载地址
+struct PlayUrl {
+    pub video: Vec<Url>,
+    pub audio: Vec<Url>,
+    /// DASH的全部音视频流，FLV/MP4为空
+    pub formats: Vec<StreamFormat>,
+    /// `video`的实际清晰度
+    pub quality: Quality,
+    /// `audio`的实际音质
+    pub audio_quality: Option<AudioQuality>,
+    /// `video`、`audio`中地址的备用镜像
+    pub backup_urls: HashMap<Url, Vec<Url>>,
+}
+
+impl PlayUrl {
+    /// 填充`VideoEntry::into_video`得到的`VideoInfo`
+    fn fill(self, video: VideoInfo) -> VideoInfo {
+        VideoInfo {
+            expires: url_deadline(self.video.iter().chain(&self.audio)),
+            video: self.video,
+            audio: self.audio,
+            formats: self.formats,
+            quality: Some(self.quality),
+            audio_quality: self.audio_quality,
+            backup_urls: self.backup_urls,
+            ..video
+        }
+    }
+}
+
+/// CDN地址中`deadline`参数的最小值，即地址的过期时间
+fn url_deadline<'a, I>(urls: I) -> Option<i64>
+where
+    I: IntoIterator<Item = &'a Url>,
+{
+    urls.into_iter()
+        .filter_map(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "deadline")
+                .and_then(|(_, deadline)| deadline.parse().ok())
+        })
+        .min()
+}
+
+/// MP4,FLV格式返回
+#[derive(Debug, Deserialize)]
+struct Durl {
+    /// 地址，存在转义
+    pub url: String,
+    /// 备用地址，存在转义
+    #[serde(default)]
+    pub backup_url: Vec<String>,
+}
+
+/// Dash 格式返回
+#[derive(Debug, Deserialize)]
+struct Dash {
+    pub video: Vec<DashItem>,
+    pub audio: Vec<DashItem>,
+    /// 杜比全景声
+    pub dolby: Option<DashDolby>,
+    /// Hi-Res无损
+    pub flac: Option<DashFlac>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashDolby {
+    /// 没有杜比音频时为`null`
+    pub audio: Option<Vec<DashItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashFlac {
+    /// 没有无损音频时为`null`
+    pub audio: Option<DashItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashItem {
+    /// 音视频清晰度
+    pub id: i32,
+    /// 下载地址
+    pub base_url: String,
+    /// 备用地址
+    #[serde(default)]
+    pub backup_url: Vec<String>,
+    /// 所需带宽
+    #[serde(rename(deserialize = "bandwidth"))]
+    pub band_width: i32,
+    /// 媒体类型
+    pub mime_type: String,
+    /// 编码/音频类型
+    pub codecs: String,
+    /// 视频宽度，杜比与无损音频中没有以下字段
+    #[serde(default)]
+    pub width: i32,
+    /// 视频高度
+    #[serde(default)]
+    pub height: i32,
+    /// 视频帧率
+    #[serde(default)]
+    pub frame_rate: String,
+}
+
+/// DASH音频的音质代码
+fn audio_quality(id: i32) -> Option<AudioQuality> {
+    match id {
+        30216 => Some(AudioQuality::K64),
+        30232 => Some(AudioQuality::K132),
+        30280 => Some(AudioQuality::K192),
+        30250 => Some(AudioQuality::Dolby),
+        30251 => Some(AudioQuality::HiRes),
+        _ => None,
+    }
+}
+
+impl DashItem {
+    fn format(&self, kind: StreamKind) -> Result<StreamFormat> {
+        Ok(StreamFormat {
+            kind,
+            id: self.id,
+            codecs: self.codecs.clone(),
+            width: self.width.max(0) as u32,
+            height: self.height.max(0) as u32,
+            frame_rate: self.frame_rate.parse().ok(),
+            bandwidth: self.band_width.max(0) as u64,
+            mime_type: self.mime_type.clone(),
+            url: BilibiliSource::parse_url(&self.base_url)?,
+            backup_urls: VideoUrlInfo::backup_urls(&self.backup_url),
+            audio_quality: match kind {
+                StreamKind::Video => None,
+                StreamKind::Audio => audio_quality(self.id),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BangumiInfo {
+    pub media: MediaInfo,
+}
+
+/// 剧集基本信息（mdID方式）
+#[derive(Debug, Deserialize)]
+struct MediaInfo {
+    pub season_id: u64,
+}
+
+/// 具体分集信息
+#[derive(Debug, Deserialize)]
+struct EpisodesInfo {
+    /// 分集
+    pub episodes: Vec<Episode>,
+    /// 简介
+    pub evaluate: String,
+    pub title: String,
+}
+
+impl EpisodesInfo {
+    /// `index`为分集在剧集中的序号
+    fn metadata(&self, episode: &Episode, index: u32) -> VideoMetadata {
+        VideoMetadata {
+            id: Some(format!("ep{}", episode.id)),
+            duration: Some(Duration::from_millis(episode.duration)),
+            resolution: episode.dimension.as_ref().and_then(Dimension::resolution),
+            published: Some(episode.pub_time).filter(|&time| time > 0),
+            description: Some(self.evaluate.clone()).filter(|evaluate| !evaluate.is_empty()),
+            series: Some(self.title.clone()),
+            episode: Some(index),
+            ..VideoMetadata::default()
+        }
+    }
+}
+
+/// 分集
+#[derive(Debug, Deserialize)]
+struct Episode {
+    pub bvid: String,
+    pub cid: u64,
+    /// 封面
+    pub cover: String,
+    /// 单集epid
+    pub id: u64,
+    /// 单集完整标题
+    pub long_title: String,
+    /// 单集标题
+    pub title: String,
+    /// 时长，毫秒
+    #[serde(default)]
+    pub duration: u64,
+    /// 发布时间（Unix时间戳，秒）
+    #[serde(default)]
+    pub pub_time: i64,
+    pub dimension: Option<Dimension>,
+}
+#[cfg(test)]
+mod test {
+    use super::{
+        super::{
+            AudioQuality, EntryKey, PartSelection, Quality, QualityFallback, Resolution,
+            ResolveOptions, Result, StreamKind, StreamPreference, VideoCodec, VideoEntry,
+            VideoInfo, VideoMetadata, VideoSource, VideoType,
+        },
+        BilibiliClient, BilibiliSource, DimensionCode, EntryLocator, PlayUrl, SpaceFilter, UrlType,
+        VideoTypeCode, FNVAL_AV1, FNVAL_DOLBY_AUDIO, REQUEST_VIDEO_INFO_URL,
+    };
+    use crate::cookie::{CookieJar, CookieWarning};
+    use crate::error::VideoSourceError;
+    use crate::source::mock::{MockRequest, MockResponse, MockRoutes, MockServer};
+    use futures::StreamExt;
+    use reqwest::{StatusCode, Url};
+    use std::convert::TryInto;
+    use std::sync::Mutex;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::{Duration, Instant};
+
+    pub(super) fn fixture(name: &str) -> String {
+        let path = format!(
+            "{}/tests/fixtures/bilibili/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    pub(super) fn mock_source(server: &MockServer) -> BilibiliSource {
+        let mut source = BilibiliSource::default();
+        source.0.origin = Some(server.url("/"));
+        source
+    }
+
+    /// 由路径与fixture文件名组成的路由表
+    pub(super) fn fixture_routes(routes: &[(&str, &str)]) -> MockRoutes {
+        routes
+            .iter()
+            .fold(MockRoutes::new(), |routes, (path, name)| {
+                routes.json(path, fixture(name))
+            })
+    }
+
+    /// 启动`routes`，返回服务器与连接到它的来源
+    pub(super) async fn mock_api(routes: MockRoutes) -> (MockServer, BilibiliSource) {
+        let server = routes.start().await;
+        let source = mock_source(&server);
+        (server, source)
+    }
+
+    pub(super) async fn list_videos(
+        source: &BilibiliSource,
+        url: &str,
+        parts: &str,
+    ) -> Vec<VideoInfo> {
+        source
+            .video_list(
+                &Url::parse(url).unwrap(),
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &parts.parse().unwrap(),
+            )
+            .unwrap()
+            .map(|video| video.unwrap())
+            .collect()
+            .await
+    }
+
+    /// 按请求的cid返回FLV地址，地址以cid结尾
+    pub(super) fn play_url_by_cid(request: &MockRequest) -> MockResponse {
+        let cid = request.query("cid").unwrap();
+        MockResponse::json(fixture("playurl_durl.json").replace("35039663-1-32", &cid))
+    }
+
+    async fn list_titles(source: &BilibiliSource, url: &str, parts: &str) -> Vec<String> {
+        list_videos(source, url, parts)
+            .await
+            .into_iter()
+            .map(|video| video.title)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn bilibili_http_get_test() {
+        let bilibili = BilibiliClient::default();
+        let url = Url::parse(REQUEST_VIDEO_INFO_URL).unwrap();
+        let result = bilibili
+            .bilibili_http_get(&url, [("bvid", "BV1ex411J7GE")].iter(), false)
+            .await
+            .unwrap();
+        assert_eq!(result.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn request_cids_test() {
+        let bilibili = BilibiliClient::default();
+        let result = bilibili.request_video_info("BV1ex411J7G1").await;
+        assert!(result.is_err());
+        assert!(matches!(result, Err(VideoSourceError::NoSuchResource(_))));
+
+        let result = bilibili.request_video_info("BVxxxxxx").await;
+        assert!(result.is_err());
+        assert!(matches!(result, Err(VideoSourceError::RequestError(_))));
+
+        let result = bilibili
+            .request_video_info("BV1ex411J7GE")
+            .await
+            .unwrap()
+            .view
+            .pages;
+        assert_ne!(result.len(), 0);
+        assert_eq!(result[0].cid, 66445301);
+        assert_eq!(result[0].part, "00. 宣传短片");
+        assert_eq!(result[0].page, 1);
+        assert_eq!(result[1].cid, 35039663);
+        assert_eq!(result[1].part, "01. 火柴人与动画师");
+    }
+
+    #[tokio::test]
+    async fn request_video_url_test() {
+        let mut bilibili = BilibiliClient::default();
+        let PlayUrl { video, audio, .. } = bilibili
+            .request_video_url(
+                "BV1y7411Q7Eq",
+                171776208,
+                VideoTypeCode::Flv1,
+                DimensionCode::P480,
+                &StreamPreference::default(),
+            )
+            .await
+            .unwrap();
+        assert!(audio.is_empty());
+        assert_eq!(video.len(), 1);
+        assert!(video[0].host_str().unwrap().ends_with("bilivideo.com"));
+
+        assert!(matches!(
+            bilibili
+                .request_video_url(
+                    "BV1y7411Q7Eq",
+                    171776208,
+                    VideoTypeCode::Flv1,
+                    DimensionCode::P1080,
+                    &StreamPreference::default(),
+                )
+                .await,
+            Err(VideoSourceError::NeedLogin)
+        ));
+        bilibili.set_token(std::env::var("BILIBILI_COOKIE").unwrap());
+        let PlayUrl { video, audio, .. } = bilibili
+            .request_video_url(
+                "BV1y7411Q7Eq",
+                171776208,
+                VideoTypeCode::Flv1,
+                DimensionCode::P1080,
+                &StreamPreference::default(),
+            )
+            .await
+            .unwrap();
+        assert!(audio.is_empty());
+        assert_eq!(video.len(), 1);
+        assert!(video[0].host_str().unwrap().ends_with("bilivideo.com"));
+
+        // 无大会员时 返回可用的最高画质
+        let PlayUrl { video, audio, .. } = bilibili
+            .request_video_url(
+                "BV1y7411Q7Eq",
+                171776208,
+                VideoTypeCode::Flv1,
+                DimensionCode::P4K,
+                &StreamPreference::default(),
+            )
+            .await
+            .unwrap();
+        assert!(audio.is_empty());
+        assert_eq!(video.len(), 1);
+        let video = video[0].to_string();
+        assert!(video.contains("bilivideo.com"));
+
+        let PlayUrl { video, audio, .. } = bilibili
+            .request_video_url(
+                "BV1y7411Q7Eq",
+                171776208,
+                VideoTypeCode::Dash,
+                DimensionCode::P1080,
+                &StreamPreference::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(audio.len(), 1);
+        assert!(audio[0].to_string().contains("bilivideo.com"));
+        assert_eq!(video.len(), 1);
+        let video = video[0].to_string();
+        assert!(
+            video.contains("bilivideo.com")
+                && (video.contains("30080.m4s") || video.contains("30077.m4s"))
+        );
+    }
+
+    #[tokio::test]
+    async fn request_bangumi_ssid_test() {
+        let bilibili = BilibiliClient::default();
+        //bilibili.set_token(std::env::var("BILIBILI_COOKIE").unwrap());
+        assert_eq!(bilibili.request_bangumi_ssid(5978).await.unwrap(), 5978);
+        assert_eq!(
+            bilibili.request_bangumi_ssid(28229053).await.unwrap(),
+            33624,
+        );
+    }
+
+    #[tokio::test]
+    async fn request_bangumi_info_test() {
+        let bilibili = BilibiliClient::default();
+        let result = bilibili.request_bangumi_info(33624).await.unwrap().episodes;
+        assert_eq!(result.len(), 36);
+        assert_eq!(result[0].cid, 200063835);
+        assert_eq!(result[0].long_title, "林黛玉别父进京都");
+
+        let result = bilibili.request_bangumi_info(5978).await.unwrap().episodes;
+        assert!(!result.is_empty());
+        assert_eq!(result[0].cid, 15915981);
+        assert_eq!(result[0].long_title, "漩涡博人");
+    }
+
+    #[test]
+    fn url_type_test() {
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/video/BVXXXXXX"
+                    .try_into()
+                    .unwrap()
+            ),
+            Some(UrlType::Video("BVXXXXXX".to_string()))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://www.bilibili.com/video/av170001".parse().unwrap()),
+            Some(UrlType::Video("BV17x411w7KC".to_string()))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://www.bilibili.com/video/AV170001/".parse().unwrap()),
+            Some(UrlType::Video("BV17x411w7KC".to_string()))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://www.bilibili.com/video/av0".parse().unwrap()),
+            None
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://m.bilibili.com/video/BVXXXXXX?p=1"
+                    .try_into()
+                    .unwrap()
+            ),
+            Some(UrlType::Video("BVXXXXXX".to_string()))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/bangumi/media/md28229053"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Bangumi(28229053))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/bangumi/play/ep327884"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Episode(327884))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/bangumi/play/ss33624?spm_id_from=333.337"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Season(33624))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/bangumi/play/ep4294967310"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Episode(4294967310))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/favlist?fid=1052622027&ftype=create"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Favorite(1052622027))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://space.bilibili.com/2/favlist".parse().unwrap()),
+            None
+        );
+        // 收藏的合集不是收藏夹
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/favlist?fid=1234&ftype=collect"
+                    .parse()
+                    .unwrap()
+            ),
+            None
+        );
+        for url in [
+            "https://space.bilibili.com/2",
+            "https://space.bilibili.com/2/",
+            "https://space.bilibili.com/2/video?tid=0",
+            "https://space.bilibili.com/2/upload/video",
+        ] {
+            assert_eq!(
+                BilibiliSource::url_type(&url.parse().unwrap()),
+                Some(UrlType::Space {
+                    mid: 2,
+                    filter: SpaceFilter::default()
+                })
+            );
+        }
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/search/video?keyword=%E7%81%AB%E6%9F%B4%E4%BA%BA"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Space {
+                mid: 2,
+                filter: SpaceFilter {
+                    keyword: Some("火柴人".to_string()),
+                    ..SpaceFilter::default()
+                }
+            })
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://space.bilibili.com/abc".parse().unwrap()),
+            None
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/channel/collectiondetail?sid=1234&ctype=0"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Collection {
+                mid: 2,
+                season_id: 1234
+            })
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/channel/seriesdetail?sid=5678"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Series {
+                mid: 2,
+                series_id: 5678
+            })
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://space.bilibili.com/2/lists/1234".parse().unwrap()),
+            Some(UrlType::Collection {
+                mid: 2,
+                season_id: 1234
+            })
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/lists/5678?type=series"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Series {
+                mid: 2,
+                series_id: 5678
+            })
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://www.bilibili.com/audio/au1986237".parse().unwrap()),
+            Some(UrlType::Audio(1986237))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://m.bilibili.com/audio/am10624".parse().unwrap()),
+            Some(UrlType::AudioMenu(10624))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://www.bilibili.com/audio/home".parse().unwrap()),
+            None
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://space.bilibili.com/2/channel/collectiondetail"
+                    .parse()
+                    .unwrap()
+            ),
+            None
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/medialist/detail/ml3173390627"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Favorite(3173390627))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/medialist/play/ml1052622027?spm_id_from=333.999"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::Favorite(1052622027))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/cheese/play/ss360"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::CourseSeason(360))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/cheese/play/ep8002?query_from=0"
+                    .parse()
+                    .unwrap()
+            ),
+            Some(UrlType::CourseEpisode(8002))
+        );
+        assert_eq!(
+            BilibiliSource::url_type(&"https://www.bilibili.com/cheese/play/360".parse().unwrap()),
+            None
+        );
+        assert_eq!(
+            BilibiliSource::url_type(
+                &"https://www.bilibili.com/bangumi/play/md28229053"
+                    .parse()
+                    .unwrap()
+            ),
+            None
+        );
+    }
+    #[tokio::test]
+    async fn request_redirect_target_test() {
+        let server = MockRoutes::new()
+            .route("/abcd", |_| MockResponse::redirect("/hop"))
+            .route("/hop", |_| {
+                MockResponse::redirect(
+                    "https://m.bilibili.com/video/BV1ex411J7GE?p=1&share_medium=android",
+                )
+            })
+            .route("/loop", |_| MockResponse::redirect("/loop"))
+            .start()
+            .await;
+        let bilibili = BilibiliClient::default();
+        let url = bilibili
+            .request_redirect_target(&server.url("/abcd"))
+            .await
+            .unwrap();
+        assert_eq!(
+            url,
+            Url::parse("https://m.bilibili.com/video/BV1ex411J7GE?p=1&share_medium=android")
+                .unwrap()
+        );
+        assert!(matches!(
+            bilibili.request_redirect_target(&server.url("/loop")).await,
+            Err(VideoSourceError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            bilibili
+                .request_redirect_target(&server.url("/deleted"))
+                .await,
+            Err(VideoSourceError::InvalidUrl(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_test() {
+        let source = BilibiliSource::default();
+        let url = Url::parse("https://www.bilibili.com/video/BV1ex411J7GE").unwrap();
+        assert_eq!(source.resolve(&url).await.unwrap(), url);
+        assert!(BilibiliSource::is_short_link(
+            &Url::parse("https://b23.tv/abcd").unwrap()
+        ));
+        assert!(!source.valid(&Url::parse("https://b23.tv/abcd").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn import_cookies_test() {
+        let (_server, mut source) = mock_api(MockRoutes::new().route(
+            "/x/web-interface/view/detail",
+            |request| {
+                // 只发送Bilibili的Cookie
+                assert_eq!(request.header("cookie"), Some("SESSDATA=abc"));
+                MockResponse::json(fixture("view_detail_single.json"))
+            },
+        ))
+        .await;
+        assert_eq!(
+            source.cookie_warnings(),
+            vec![
+                CookieWarning::Missing("SESSDATA".to_string()),
+                CookieWarning::Missing("bili_jct".to_string()),
+            ]
+        );
+
+        let jar = CookieJar::from_netscape(
+            ".bilibili.com\tTRUE\t/\tFALSE\t4102444800\tSESSDATA\tabc\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t1600000000\tbili_jct\told\n\
+             .example.com\tTRUE\t/\tFALSE\t4102444800\tother\t1\n",
+        )
+        .unwrap();
+        let warnings = source.import_cookies(jar);
+        assert_eq!(
+            warnings,
+            vec![CookieWarning::Expired("bili_jct".to_string())]
+        );
+        assert_eq!(warnings[0].to_string(), "Cookie已过期: bili_jct");
+        assert_eq!(source.token(), Some("SESSDATA=abc"));
+        assert_eq!(
+            source.cookie_warnings(),
+            vec![CookieWarning::Missing("bili_jct".to_string())]
+        );
+
+        let entries: Vec<_> = source
+            .entries(
+                &Url::parse("https://www.bilibili.com/video/BV17x411w7KC").unwrap(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert!(entries[0].is_ok());
+    }
+
+    #[tokio::test]
+    async fn entries_test() {
+        let play_url_requests = Arc::new(AtomicUsize::new(0));
+        let detail_requests = Arc::new(AtomicUsize::new(0));
+        let (counter, detail_counter) = (play_url_requests.clone(), detail_requests.clone());
+        let routes = MockRoutes::new()
+            .route("/x/web-interface/view/detail", move |_| {
+                detail_counter.fetch_add(1, Ordering::SeqCst);
+                MockResponse::json(fixture("view_detail.json"))
+            })
+            .route("/x/player/playurl", move |request| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                assert_eq!(request.query("cid").as_deref(), Some("35039663"));
+                MockResponse::json(fixture("playurl_durl.json"))
+            });
+        let (_server, source) = mock_api(routes).await;
+
+        // 列出条目时不请求下载地址
+        let entries: Vec<_> = source
+            .entries(
+                &Url::parse("https://www.bilibili.com/video/BV1ex411J7GE").unwrap(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect()
+            .await;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[1].key.get(),
+            Some(&EntryLocator::Video {
+                bvid: "BV1ex411J7GE".to_string(),
+                cid: Some(35039663),
+            })
+        );
+        assert!(source.expand_entry(&entries[1]).is_none());
+        assert_eq!(play_url_requests.load(Ordering::SeqCst), 0);
+
+        let video = source
+            .resolve_entry(
+                &entries[1],
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(video.title, entries[1].title);
+        assert_eq!(video.metadata, entries[1].metadata);
+        assert_eq!(video.video.len(), 1);
+        assert_eq!(play_url_requests.load(Ordering::SeqCst), 1);
+        // 地址中的`deadline`
+        assert_eq!(video.expires, Some(1700000000));
+        assert!(video.expires_within(Duration::from_secs(0)));
+        assert_eq!(video.handle.entry, entries[1]);
+
+        // 过期后只重新请求下载地址
+        let refreshed = video.refresh(&source).await.unwrap();
+        assert_eq!(refreshed.video, video.video);
+        assert_eq!(refreshed.handle, video.handle);
+        assert_eq!(play_url_requests.load(Ordering::SeqCst), 2);
+        assert_eq!(detail_requests.load(Ordering::SeqCst), 1);
+
+        let keys = [
+            EntryKey::new("video:BV1ex411J7GE:35039663".to_string()),
+            EntryKey::new(35039663u64),
+            EntryKey::new(()),
+            // 未展开的多P视频
+            EntryKey::new(EntryLocator::Video {
+                bvid: "BV1ex411J7GE".to_string(),
+                cid: None,
+            }),
+        ];
+        for key in keys {
+            let entry = VideoEntry {
+                key,
+                ..entries[0].clone()
+            };
+            let result = source
+                .resolve_entry(
+                    &entry,
+                    VideoType::Flv,
+                    &DimensionCode::P480.quality(),
+                    &StreamPreference::default(),
+                )
+                .await;
+            assert!(matches!(result, Err(VideoSourceError::InvalidEntry(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn large_id_test() {
+        let routes =
+            fixture_routes(&[("/x/web-interface/view/detail", "view_detail_large_id.json")])
+                .route("/pgc/review/user", |request| {
+                    assert_eq!(request.query("media_id").as_deref(), Some("4294967301"));
+                    MockResponse::json(fixture("bangumi_media_large_id.json"))
+                })
+                .route("/pgc/view/web/season", |request| {
+                    assert_eq!(request.query("season_id").as_deref(), Some("4294967302"));
+                    MockResponse::json(fixture("season_large_id.json"))
+                })
+                .route("/x/player/playurl", play_url_by_cid);
+        let (_server, source) = mock_api(routes).await;
+
+        // cid超过`i32::MAX`
+        let videos = list_videos(&source, "https://www.bilibili.com/video/BV1Lx4y1o7Ls", "").await;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(
+            videos[0].handle.entry.key.get(),
+            Some(&EntryLocator::Video {
+                bvid: "BV1Lx4y1o7Ls".to_string(),
+                cid: Some(25632479215),
+            })
+        );
+        assert!(videos[0].video[0].path().ends_with("/25632479215.flv"));
+
+        let videos = list_videos(
+            &source,
+            "https://www.bilibili.com/bangumi/media/md4294967301",
+            "3",
+        )
+        .await;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].metadata.id.as_deref(), Some("ep4294967312"));
+        assert!(videos[0].video[0].path().ends_with("/25632479217.flv"));
+    }
+
+    #[tokio::test]
+    async fn entry_error_test() {
+        let routes = MockRoutes::new()
+            .json(
+                "/pgc/view/web/season",
+                fixture("season.json").replace("http://i0.hdslb.com/bfs/archive/0a2c", "0a2c"),
+            )
+            .route("/x/player/playurl", |request| {
+                match request.query("cid").as_deref() {
+                    Some("200064507") => {
+                        MockResponse::json(r#"{"code":-404,"message":"啥都木有"}"#)
+                    }
+                    _ => MockResponse::json(fixture("playurl_durl.json")),
+                }
+            });
+        let (_server, source) = mock_api(routes).await;
+
+        // 第一集封面错误，第三集无法获取下载地址，其余分集不受影响
+        let results: Vec<_> = source
+            .video_list(
+                &Url::parse("https://www.bilibili.com/bangumi/play/ss33624").unwrap(),
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 3);
+        assert!(results[1].is_ok());
+        match &results[0] {
+            Err(VideoSourceError::Entry { id, title, source }) => {
+                assert_eq!(
+                    (id.as_str(), title.as_str()),
+                    ("ep327584", "1 林黛玉别父进京都")
+                );
+                assert!(matches!(**source, VideoSourceError::InvalidApiData(_)));
+            }
+            result => panic!("{:?}", result),
+        }
+        let error = results[2].as_ref().unwrap_err();
+        assert!(error.is_entry());
+        assert!(error
+            .to_string()
+            .starts_with("3 贾宝玉梦游太虚境（ep327586）: "));
+        assert!(matches!(error.inner(), VideoSourceError::NoSuchResource(_)));
+    }
+
+    #[tokio::test]
+    async fn video_list_concurrency_test() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        let routes = fixture_routes(&[("/x/web-interface/view/detail", "view_detail.json")]).route(
+            "/x/player/playurl",
+            move |request| {
+                log.lock().unwrap().push(Instant::now());
+                // 第一P最慢
+                let delay = match request.query("cid").as_deref() {
+                    Some("66445301") => 300,
+                    _ => 0,
+                };
+                play_url_by_cid(request).with_delay(Duration::from_millis(delay))
+            },
+        );
+        let (_server, mut source) = mock_api(routes).await;
+        source.set_resolve_options(ResolveOptions {
+            concurrency: 2,
+            interval: Duration::from_millis(50),
+        });
+
+        let videos = list_videos(&source, "https://www.bilibili.com/video/BV1ex411J7GE", "").await;
+        let files: Vec<_> = videos
+            .iter()
+            .map(|video| video.video[0].path_segments().unwrap().next_back().unwrap())
+            .collect();
+        assert_eq!(files, ["66445301.flv", "35039663.flv", "35039678.flv"]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        // 第二P不等待第一P完成，但间隔不小于`interval`
+        let second = requests[1] - requests[0];
+        assert!(second >= Duration::from_millis(40), "{:?}", second);
+        assert!(second < Duration::from_millis(300), "{:?}", second);
+        // 同时最多两个请求，第三P在第一P完成后开始
+        assert!(requests[2] - requests[0] >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn video_list_parts_test() {
+        let play_url_requests = Arc::new(AtomicUsize::new(0));
+        let counter = play_url_requests.clone();
+        let routes = fixture_routes(&[
+            ("/x/web-interface/view/detail", "view_detail.json"),
+            ("/pgc/view/web/season", "season.json"),
+        ])
+        .route("/x/player/playurl", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::json(fixture("playurl_durl.json"))
+        });
+        let (_server, source) = mock_api(routes).await;
+
+        let titles = list_titles(
+            &source,
+            "https://www.bilibili.com/video/BV1ex411J7GE?p=2",
+            "",
+        )
+        .await;
+        assert_eq!(titles, vec!["01. 火柴人与动画师"]);
+        assert_eq!(play_url_requests.load(Ordering::SeqCst), 1);
+
+        let titles = list_titles(
+            &source,
+            "https://www.bilibili.com/video/BV1ex411J7GE?p=2",
+            "1,3-",
+        )
+        .await;
+        assert_eq!(titles, vec!["00. 宣传短片", "02. 火柴人与动画师 II"]);
+        assert_eq!(play_url_requests.load(Ordering::SeqCst), 3);
+
+        let titles = list_titles(&source, "https://www.bilibili.com/video/BV1ex411J7GE", "").await;
+        assert_eq!(titles.len(), 3);
+
+        let titles = list_titles(
+            &source,
+            "https://www.bilibili.com/bangumi/play/ss33624",
+            "2-",
+        )
+        .await;
+        assert_eq!(titles, vec!["2 宝黛初会情意绵", "3 贾宝玉梦游太虚境"]);
+    }
+
+    #[tokio::test]
+    async fn video_formats_test() {
+        let (_server, mut source) = mock_api(fixture_routes(&[
+            ("/x/web-interface/view/detail", "view_detail_single.json"),
+            ("/x/player/playurl", "playurl_dash.json"),
+        ]))
+        .await;
+        // 1080P需要登录
+        source.set_token("SESSDATA=test".to_string());
+        let videos: Vec<_> = source
+            .video_list(
+                &Url::parse("https://www.bilibili.com/video/BV17x411w7KC").unwrap(),
+                VideoType::MP4,
+                &DimensionCode::P1080.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap()
+            .map(|video| video.unwrap())
+            .collect()
+            .await;
+        let video = &videos[0];
+        assert_eq!(video.formats.len(), 5);
+        let format = &video.formats[1];
+        assert_eq!(
+            (format.kind, format.id, format.width, format.height),
+            (StreamKind::Video, 80, 1484, 1080)
+        );
+        assert_eq!(format.codec(), Some(VideoCodec::Hevc));
+        assert_eq!(format.frame_rate, Some(29.412));
+        assert_eq!(format.bandwidth, 720328);
+        assert_eq!(format.mime_type, "video/mp4");
+        // 默认选择请求的清晰度中的第一个视频与192K音频
+        assert_eq!(video.video, vec![video.formats[0].url.clone()]);
+        assert_eq!(video.audio, vec![video.formats[4].url.clone()]);
+        assert_eq!(video.audio_quality, Some(AudioQuality::K192));
+        // 备用镜像
+        let mirrors = video.mirrors(&video.video[0]);
+        assert_eq!(mirrors.len(), 2);
+        assert_eq!(mirrors[0], video.video[0]);
+        assert_eq!(
+            mirrors[1].host_str(),
+            Some("upos-sz-mirrorhw.bilivideo.com")
+        );
+        assert_eq!(video.formats[1].mirrors().len(), 2);
+        assert_eq!(video.mirrors(&video.audio[0]), video.audio);
+
+        let resolve = |preference: &str| {
+            let preference: StreamPreference = preference.parse().unwrap();
+            let source = &source;
+            async move {
+                source
+                    .video_list(
+                        &Url::parse("https://www.bilibili.com/video/BV17x411w7KC").unwrap(),
+                        VideoType::MP4,
+                        &DimensionCode::P1080.quality(),
+                        &preference,
+                        &PartSelection::all(),
+                    )
+                    .unwrap()
+                    .map(|video| video.unwrap())
+                    .collect::<Vec<_>>()
+                    .await
+                    .remove(0)
+            }
+        };
+        let video = resolve("<=1080p,hevc").await;
+        assert_eq!(video.video, vec![video.formats[1].url.clone()]);
+        // 高于720P的视频流不可接受，回退到720P
+        let video = resolve("720p").await;
+        assert_eq!(video.video, vec![video.formats[2].url.clone()]);
+        assert_eq!(video.quality, Some(DimensionCode::P720.quality()));
+    }
+
+    #[tokio::test]
+    async fn video_audio_quality_test() {
+        let routes = fixture_routes(&[("/x/web-interface/view/detail", "view_detail_single.json")])
+            .route("/x/player/playurl", |request| {
+                let fnval: u32 = request.query("fnval").unwrap().parse().unwrap();
+                if fnval & FNVAL_DOLBY_AUDIO != 0 {
+                    MockResponse::json(fixture("playurl_dash_hifi.json"))
+                } else {
+                    MockResponse::json(fixture("playurl_dash.json"))
+                }
+            });
+        let (_server, mut source) = mock_api(routes).await;
+        source.set_token("SESSDATA=test".to_string());
+        let list = |audio: AudioQuality| {
+            source
+                .video_list(
+                    &Url::parse("https://www.bilibili.com/video/BV17x411w7KC").unwrap(),
+                    VideoType::MP4,
+                    &DimensionCode::P720.quality(),
+                    &StreamPreference {
+                        audio,
+                        ..StreamPreference::default()
+                    },
+                    &PartSelection::all(),
+                )
+                .unwrap()
+                .map(|video| video.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let delivered = |video: &VideoInfo| {
+            let format = video
+                .formats
+                .iter()
+                .find(|format| video.audio == vec![format.url.clone()])
+                .unwrap();
+            assert_eq!(format.audio_quality, video.audio_quality);
+            (format.id, video.audio_quality)
+        };
+
+        let videos = list(AudioQuality::K64).await;
+        assert_eq!(delivered(&videos[0]), (30216, Some(AudioQuality::K64)));
+        // 没有132K时选择64K
+        let videos = list(AudioQuality::K132).await;
+        assert_eq!(delivered(&videos[0]), (30216, Some(AudioQuality::K64)));
+
+        let videos = list(AudioQuality::HiRes).await;
+        let video = &videos[0];
+        assert_eq!(
+            video
+                .formats
+                .iter()
+                .filter_map(|format| format.audio_quality)
+                .collect::<Vec<_>>(),
+            vec![
+                AudioQuality::K64,
+                AudioQuality::K132,
+                AudioQuality::K192,
+                AudioQuality::Dolby,
+                AudioQuality::HiRes
+            ]
+        );
+        assert_eq!(delivered(video), (30251, Some(AudioQuality::HiRes)));
+        let videos = list(AudioQuality::Dolby).await;
+        assert_eq!(delivered(&videos[0]), (30250, Some(AudioQuality::Dolby)));
+        assert_eq!(videos[0].formats[6].codecs, "ec-3");
+    }
+
+    #[tokio::test]
+    async fn video_codec_preference_test() {
+        let routes = fixture_routes(&[("/x/web-interface/view/detail", "view_detail_single.json")])
+            .route("/x/player/playurl", |request| {
+                // 需要AV1时附加AV1的功能位
+                let fnval = request.query("fnval").unwrap();
+                assert!(fnval == "16" || fnval == (16 | FNVAL_AV1).to_string());
+                MockResponse::json(fixture("playurl_dash.json"))
+            });
+        let (_server, mut source) = mock_api(routes).await;
+        source.set_token("SESSDATA=test".to_string());
+        let list = |codecs: Vec<VideoCodec>, dimension: DimensionCode| {
+            source
+                .video_list(
+                    &Url::parse("https://www.bilibili.com/video/BV17x411w7KC").unwrap(),
+                    VideoType::MP4,
+                    &dimension.quality(),
+                    &StreamPreference {
+                        codecs,
+                        ..StreamPreference::default()
+                    },
+                    &PartSelection::all(),
+                )
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+
+        let videos = list(
+            vec![VideoCodec::Hevc, VideoCodec::Avc],
+            DimensionCode::P1080,
+        )
+        .await;
+        let video = videos[0].as_ref().unwrap();
+        assert_eq!(video.video, vec![video.formats[1].url.clone()]);
+        let videos = list(vec![VideoCodec::Av1, VideoCodec::Avc], DimensionCode::P1080).await;
+        let video = videos[0].as_ref().unwrap();
+        assert_eq!(video.video, vec![video.formats[0].url.clone()]);
+        // 720P只有AVC，没有可接受的编码
+        let videos = list(vec![VideoCodec::Av1, VideoCodec::Hevc], DimensionCode::P720).await;
+        assert!(matches!(
+            &videos[..],
+            [Err(error)] if matches!(error.inner(), VideoSourceError::NoSuchResource(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn video_quality_fallback_test() {
+        let routes = fixture_routes(&[("/x/web-interface/view/detail", "view_detail_single.json")])
+            .route("/x/player/playurl", |request| {
+                match request.query("fnval").as_deref() {
+                    Some("16") => MockResponse::json(fixture("playurl_dash.json")),
+                    _ => MockResponse::json(fixture("playurl_durl.json")),
+                }
+            });
+        let (_server, mut source) = mock_api(routes).await;
+        source.set_token("SESSDATA=test".to_string());
+        let list = |video_type: VideoType, dimension: i32, fallback: QualityFallback| {
+            source
+                .video_list(
+                    &Url::parse("https://www.bilibili.com/video/BV17x411w7KC").unwrap(),
+                    video_type,
+                    &DimensionCode::from(dimension).quality(),
+                    &StreamPreference {
+                        fallback,
+                        ..StreamPreference::default()
+                    },
+                    &PartSelection::all(),
+                )
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        let quality = |videos: Vec<Result<VideoInfo>>| match &videos[..] {
+            [Ok(video)] => Some(i32::from(DimensionCode::from(
+                video.quality.as_ref().unwrap(),
+            ))),
+            [Err(error)] if matches!(error.inner(), VideoSourceError::NoSuchResource(_)) => None,
+            _ => panic!("{:?}", videos),
+        };
+
+        // DASH中有80、64
+        let p1080p = DimensionCode::P1080P.into();
+        let p480 = DimensionCode::P480.into();
+        assert_eq!(
+            quality(list(VideoType::MP4, 80, QualityFallback::Fail).await),
+            Some(80)
+        );
+        assert_eq!(
+            quality(list(VideoType::MP4, p1080p, QualityFallback::Lower).await),
+            Some(80)
+        );
+        assert_eq!(
+            quality(list(VideoType::MP4, p1080p, QualityFallback::Higher).await),
+            None
+        );
+        assert_eq!(
+            quality(list(VideoType::MP4, p480, QualityFallback::Higher).await),
+            Some(64)
+        );
+        assert_eq!(
+            quality(list(VideoType::MP4, p480, QualityFallback::Lower).await),
+            None
+        );
+        assert_eq!(
+            quality(list(VideoType::MP4, 74, QualityFallback::Fail).await),
+            None
+        );
+        // FLV的实际清晰度为32
+        assert_eq!(
+            quality(list(VideoType::Flv, 80, QualityFallback::Lower).await),
+            Some(32)
+        );
+        assert_eq!(
+            quality(list(VideoType::Flv, 80, QualityFallback::Fail).await),
+            None
+        );
+        assert_eq!(
+            quality(list(VideoType::Flv, 16, QualityFallback::Higher).await),
+            Some(32)
+        );
+    }
+
+    #[test]
+    fn dimension_code_test() {
+        assert_eq!(DimensionCode::from(80), DimensionCode::P1080);
+        // 未知的代码保留原值
+        assert_eq!(DimensionCode::from(127), DimensionCode::P8K);
+        assert_eq!(DimensionCode::from(200), DimensionCode::Unknown(200));
+        assert_eq!(i32::from(DimensionCode::from(200)), 200);
+        assert!(DimensionCode::Unknown(200).need_login());
+        assert!(!DimensionCode::P480.need_login());
+        assert_eq!(DimensionCode::P1080P.to_string(), "1080P+ 高清（大会员）");
+        for dimension in DimensionCode::KNOWN.iter() {
+            assert_eq!(DimensionCode::from(&dimension.quality()), *dimension);
+        }
+        // 没有对应的清晰度时选择较低者
+        let quality = Quality {
+            frame_rate: 60,
+            ..Quality::new(2160, "")
+        };
+        assert_eq!(DimensionCode::from(&quality), DimensionCode::P4K);
+        let quality = Quality {
+            hdr: true,
+            ..Quality::new(1080, "")
+        };
+        assert_eq!(DimensionCode::from(&quality), DimensionCode::P1080);
+        assert_eq!(
+            DimensionCode::from(&Quality::new(144, "")),
+            DimensionCode::P240
+        );
+        let hdr = Quality {
+            hdr: true,
+            ..Quality::new(2160, "")
+        };
+        assert_eq!(DimensionCode::from(&hdr), DimensionCode::Hdr);
+        let dolby = Quality { dolby: true, ..hdr };
+        assert_eq!(DimensionCode::from(&dolby), DimensionCode::DolbyVision);
+        assert_eq!(
+            DimensionCode::from(&Quality::new(4320, "")),
+            DimensionCode::P8K
+        );
+        // 不要求HDR时不选择HDR与杜比视界
+        let quality = Quality {
+            frame_rate: 60,
+            ..Quality::new(2160, "")
+        };
+        assert_eq!(DimensionCode::from(&quality), DimensionCode::P4K);
+        assert!(DimensionCode::P8K.need_login());
+
+        // 请求的清晰度及以下的功能位
+        let preference = StreamPreference::default();
+        assert_eq!(
+            VideoTypeCode::Dash.fnval(DimensionCode::P1080, &preference),
+            16
+        );
+        assert_eq!(
+            VideoTypeCode::Dash.fnval(DimensionCode::P4K, &preference),
+            16 | 128
+        );
+        assert_eq!(
+            VideoTypeCode::Dash.fnval(DimensionCode::DolbyVision, &preference),
+            16 | 128 | 64 | 512
+        );
+        assert_eq!(
+            VideoTypeCode::Dash.fnval(DimensionCode::P8K, &preference),
+            16 | 128 | 1024
+        );
+        assert_eq!(
+            VideoTypeCode::Dash.fnval(DimensionCode::Hdr, &preference),
+            16 | 128 | 64
+        );
+        assert_eq!(
+            VideoTypeCode::Flv1.fnval(DimensionCode::P8K, &preference),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn video_metadata_test() {
+        let routes = fixture_routes(&[
+            ("/pgc/view/web/season", "season.json"),
+            ("/x/player/playurl", "playurl_durl.json"),
+        ])
+        .route("/x/web-interface/view/detail", |request| {
+            match request.query("bvid").as_deref() {
+                Some("BV1ex411J7GE") => MockResponse::json(fixture("view_detail.json")),
+                _ => MockResponse::json(fixture("view_detail_single.json")),
+            }
+        });
+        let (_server, source) = mock_api(routes).await;
+
+        let videos = list_videos(&source, "https://www.bilibili.com/video/BV1ex411J7GE", "").await;
+        let mirrors = videos[0].mirrors(&videos[0].video[0]);
+        assert_eq!(
+            mirrors[1].host_str(),
+            Some("upos-sz-mirrorhw.bilivideo.com")
+        );
+        assert_eq!(
+            videos[0].metadata,
+            VideoMetadata {
+                id: Some("BV1ex411J7GE".to_string()),
+                part: Some(1),
+                duration: Some(Duration::from_secs(33)),
+                resolution: Some(Resolution {
+                    width: 1920,
+                    height: 1080
+                }),
+                uploader: Some("碧诗".to_string()),
+                published: Some(1609459200),
+                description: Some("Alan Becker的火柴人系列动画".to_string()),
+                tags: vec!["火柴人".to_string(), "动画".to_string()],
+                series: None,
+                episode: None,
+                series_url: None,
+            }
+        );
+        // 竖屏分P宽高对换
+        assert_eq!(
+            videos[2].metadata.resolution,
+            Some(Resolution {
+                width: 1484,
+                height: 1080
+            })
+        );
+
+        let videos = list_videos(&source, "https://www.bilibili.com/video/BV17x411w7KC", "").await;
+        let metadata = &videos[0].metadata;
+        assert_eq!(metadata.resolution, None);
+        assert_eq!(metadata.description, None);
+        assert!(metadata.tags.is_empty());
+
+        let videos = list_videos(
+            &source,
+            "https://www.bilibili.com/bangumi/play/ep327585",
+            "",
+        )
+        .await;
+        assert_eq!(videos.len(), 1);
+        let metadata = &videos[0].metadata;
+        assert_eq!(metadata.id.as_deref(), Some("ep327585"));
+        assert_eq!(metadata.series.as_deref(), Some("红楼梦"));
+        assert_eq!(metadata.episode, Some(2));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(2707)));
+        assert_eq!(metadata.published, Some(1593532800));
+    }
+
+    #[tokio::test]
+    async fn bilibili_source_video_type_test() {
+        let source = BilibiliSource::default();
+        let mut videos_info = source
+            .video_list(
+                &Url::parse("https://www.bilibili.com/bangumi/media/md28229053").unwrap(),
+                VideoType::MP4,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap();
+        while let Some(video) = videos_info.next().await {
+            let video = video.unwrap();
+            println!("{:?}", video);
+        }
+    }
+}
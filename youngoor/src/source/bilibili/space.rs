@@ -0,0 +1,261 @@
+//! UP主投稿
+
+use super::{wbi::WbiKey, BilibiliClient, BilibiliSource};
+use crate::source::{PartSelection, Result, VideoEntryStream, VideoMetadata};
+
+use reqwest::Url;
+use serde::Deserialize;
+
+const REQUEST_SPACE_VIDEO_URL: &str = "https://api.bilibili.com/x/space/wbi/arc/search";
+/// 每页数量，接口上限为50
+const SPACE_PAGE_SIZE: u32 = 30;
+
+/// 投稿筛选条件
+///
+/// 空间搜索链接中的`keyword`参数对应`keyword`，发布时间范围只能通过`space_video_list`指定
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpaceFilter {
+    /// 标题关键词
+    pub keyword: Option<String>,
+    /// 发布时间不早于（Unix时间戳，秒）
+    pub published_after: Option<i64>,
+    /// 发布时间不晚于（Unix时间戳，秒）
+    pub published_before: Option<i64>,
+}
+
+impl SpaceFilter {
+    /// 读取空间链接中的搜索关键词
+    pub(super) fn from_url(url: &Url) -> Self {
+        Self {
+            keyword: url
+                .query_pairs()
+                .find(|(key, _)| key == "keyword")
+                .map(|(_, value)| value.into_owned())
+                .filter(|value| !value.is_empty()),
+            ..Self::default()
+        }
+    }
+
+    fn contains(&self, created: i64) -> bool {
+        self.published_after.is_none_or(|after| created >= after)
+            && self.published_before.is_none_or(|before| created <= before)
+    }
+}
+
+impl BilibiliClient {
+    /// 按发布时间从新到旧请求一页投稿，`page`从1开始
+    async fn request_space_page(
+        &self,
+        key: &WbiKey,
+        mid: u64,
+        keyword: Option<&str>,
+        page: u32,
+    ) -> Result<SpaceVideoList> {
+        let url = self.api_url(REQUEST_SPACE_VIDEO_URL)?;
+        let query_params = key.sign(&[
+            ("mid", mid.to_string()),
+            ("pn", page.to_string()),
+            ("ps", SPACE_PAGE_SIZE.to_string()),
+            ("order", "pubdate".to_string()),
+            ("keyword", keyword.unwrap_or_default().to_string()),
+        ]);
+        self.bilibili_http_get_not_null(&url, query_params.iter(), self.has_cookie())
+            .await
+    }
+}
+
+impl BilibiliSource {
+    /// 逐页请求UP主的投稿，按发布时间从新到旧依次返回每个视频，获取音视频流时再展开为各分P
+    ///
+    /// `filter`可指定标题关键词与发布时间范围（Unix时间戳，秒，包含两端），如只下载某段时间内
+    /// 的投稿。`parts`按筛选后的位置选择视频
+    pub fn space_video_list(
+        &self,
+        mid: u64,
+        filter: SpaceFilter,
+        parts: PartSelection,
+    ) -> VideoEntryStream<'_> {
+        use async_stream::stream;
+
+        Box::pin(stream! {
+            let key = match self.0.request_wbi_key().await {
+                Ok(key) => key,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            let mut page = 1;
+            let mut index = 0;
+            let mut listed = 0;
+            'pages: loop {
+                let list = match self
+                    .0
+                    .request_space_page(&key, mid, filter.keyword.as_deref(), page)
+                    .await
+                {
+                    Ok(list) => list,
+                    Err(error) => {
+                        yield Err(error);
+                        break;
+                    }
+                };
+                let videos = list.list.vlist;
+                listed += videos.len() as u32;
+                let last_page = videos.is_empty() || listed >= list.page.count;
+                for video in videos {
+                    // 结果按发布时间倒序，早于下限时后续投稿均不满足条件
+                    if filter.published_after.is_some_and(|after| video.created < after) {
+                        break 'pages;
+                    }
+                    if !filter.contains(video.created) {
+                        continue;
+                    }
+                    index += 1;
+                    if !parts.contains(index) {
+                        continue;
+                    }
+                    yield Ok(Self::list_video(
+                        video.bvid,
+                        video.title,
+                        Url::parse(&video.pic).ok(),
+                        VideoMetadata {
+                            published: Some(video.created),
+                            ..VideoMetadata::default()
+                        },
+                    ));
+                }
+                if last_page {
+                    break;
+                }
+                page += 1;
+            }
+        })
+    }
+}
+
+/// 投稿列表
+#[derive(Debug, Deserialize)]
+struct SpaceVideoList {
+    pub list: SpaceVideos,
+    pub page: SpacePage,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceVideos {
+    pub vlist: Vec<SpaceVideo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpacePage {
+    /// 投稿总数
+    pub count: u32,
+}
+
+/// 投稿视频
+#[derive(Debug, Deserialize)]
+struct SpaceVideo {
+    pub bvid: String,
+    pub title: String,
+    /// 封面
+    pub pic: String,
+    /// 发布时间（Unix时间戳，秒）
+    pub created: i64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{
+        test::{fixture, fixture_routes, mock_api},
+        DimensionCode,
+    };
+    use super::SpaceFilter;
+    use crate::source::mock::{MockResponse, MockRoutes};
+    use crate::source::{PartSelection, StreamPreference, VideoSource, VideoType};
+    use futures::StreamExt;
+    use reqwest::Url;
+    use std::sync::{Arc, Mutex};
+
+    fn space_routes(keywords: Arc<Mutex<Vec<String>>>) -> MockRoutes {
+        fixture_routes(&[
+            ("/x/web-interface/nav", "nav.json"),
+            ("/x/web-interface/view/detail", "view_detail_single.json"),
+            ("/x/player/playurl", "playurl_durl.json"),
+        ])
+        .route("/x/space/wbi/arc/search", move |request| {
+            assert!(request.query("w_rid").is_some() && request.query("wts").is_some());
+            keywords
+                .lock()
+                .unwrap()
+                .push(request.query("keyword").unwrap_or_default());
+            match request.query("pn").as_deref() {
+                Some("1") => MockResponse::json(fixture("space_page1.json")),
+                Some("2") => MockResponse::json(fixture("space_page2.json")),
+                _ => MockResponse::status(404),
+            }
+        })
+    }
+
+    async fn titles(stream: crate::source::VideoInfoStream<'_>) -> Vec<String> {
+        stream.map(|video| video.unwrap().title).collect().await
+    }
+
+    async fn entry_titles(stream: crate::source::VideoEntryStream<'_>) -> Vec<String> {
+        stream.map(|entry| entry.unwrap().title).collect().await
+    }
+
+    #[tokio::test]
+    async fn space_video_list_test() {
+        let keywords = Arc::new(Mutex::new(Vec::new()));
+        let (_server, source) = mock_api(space_routes(keywords.clone())).await;
+
+        let url = Url::parse("https://space.bilibili.com/2/video").unwrap();
+        let stream = source
+            .video_list(
+                &url,
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &PartSelection::all(),
+            )
+            .unwrap();
+        assert_eq!(
+            titles(stream).await,
+            vec!["投稿四", "投稿三", "投稿二", "投稿一"]
+        );
+
+        let url = Url::parse("https://space.bilibili.com/2/search/video?keyword=投稿").unwrap();
+        let stream = source
+            .video_list(
+                &url,
+                VideoType::Flv,
+                &DimensionCode::P480.quality(),
+                &StreamPreference::default(),
+                &"2".parse().unwrap(),
+            )
+            .unwrap();
+        assert_eq!(titles(stream).await, vec!["投稿三"]);
+        assert_eq!(keywords.lock().unwrap().last().unwrap(), "投稿");
+
+        // 投稿三: 1614556800, 投稿二: 1612137600
+        let filter = SpaceFilter {
+            published_after: Some(1612137600),
+            published_before: Some(1614556800),
+            ..SpaceFilter::default()
+        };
+        keywords.lock().unwrap().clear();
+        let stream = source.space_video_list(2, filter, PartSelection::all());
+        assert_eq!(entry_titles(stream).await, vec!["投稿三", "投稿二"]);
+        // 第二页已早于下限，无需继续请求
+        assert_eq!(keywords.lock().unwrap().len(), 2);
+
+        let filter = SpaceFilter {
+            published_after: Some(1614556801),
+            ..SpaceFilter::default()
+        };
+        keywords.lock().unwrap().clear();
+        let stream = source.space_video_list(2, filter, PartSelection::all());
+        assert_eq!(entry_titles(stream).await, vec!["投稿四"]);
+        assert_eq!(keywords.lock().unwrap().len(), 1);
+    }
+}
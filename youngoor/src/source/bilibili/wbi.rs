@@ -0,0 +1,173 @@
+//! WBI签名，部分接口（如投稿列表）要求请求参数带有`w_rid`和`wts`
+
+use super::{BilibiliClient, Response};
+use crate::error::VideoSourceError;
+use crate::source::Result;
+
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REQUEST_NAV_URL: &str = "https://api.bilibili.com/x/web-interface/nav";
+
+const MIXIN_KEY_ENC_TAB: [usize; 64] = [
+    46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
+    28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60, 51, 30, 4, 22, 25,
+    54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
+];
+
+/// 签名用的密钥，每日更新
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct WbiKey {
+    img_key: String,
+    sub_key: String,
+}
+
+impl WbiKey {
+    fn mixin_key(&self) -> String {
+        let raw: Vec<char> = format!("{}{}", self.img_key, self.sub_key)
+            .chars()
+            .collect();
+        MIXIN_KEY_ENC_TAB
+            .iter()
+            .filter_map(|&index| raw.get(index))
+            .take(32)
+            .collect()
+    }
+
+    /// 为参数追加`wts`和`w_rid`
+    pub fn sign(&self, params: &[(&str, String)]) -> Vec<(String, String)> {
+        let wts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        self.sign_at(params, wts)
+    }
+
+    fn sign_at(&self, params: &[(&str, String)], wts: u64) -> Vec<(String, String)> {
+        let mut params: Vec<(String, String)> = params
+            .iter()
+            .map(|(key, value)| {
+                let value = value
+                    .chars()
+                    .filter(|c| !matches!(c, '!' | '\'' | '(' | ')' | '*'))
+                    .collect();
+                (key.to_string(), value)
+            })
+            .chain(std::iter::once(("wts".to_string(), wts.to_string())))
+            .collect();
+        params.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let query: Vec<String> = params
+            .iter()
+            .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+            .collect();
+        let digest = Md5::digest(format!("{}{}", query.join("&"), self.mixin_key()).as_bytes());
+        let w_rid = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        params.push(("w_rid".to_string(), w_rid));
+        params
+    }
+}
+
+/// 与`encodeURIComponent`一致的编码，空格编码为`%20`
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+impl BilibiliClient {
+    /// 请求WBI密钥，未登录时接口返回`-101`但仍带有密钥
+    pub(super) async fn request_wbi_key(&self) -> Result<WbiKey> {
+        let url = self.api_url(REQUEST_NAV_URL)?;
+        let response = self
+            .bilibili_http_get(&url, std::iter::empty::<(&str, &str)>(), false)
+            .await?;
+        let result: Response<Nav> = response.json().await?;
+        let message = result.message();
+        let wbi_img = result
+            .data
+            .ok_or(VideoSourceError::InvalidApiData(message))?
+            .wbi_img;
+        Ok(WbiKey {
+            img_key: Self::wbi_key_name(&wbi_img.img_url)?,
+            sub_key: Self::wbi_key_name(&wbi_img.sub_url)?,
+        })
+    }
+
+    /// 取图片地址的文件名（不含扩展名）
+    fn wbi_key_name(url: &str) -> Result<String> {
+        url.rsplit('/')
+            .next()
+            .and_then(|name| name.split('.').next())
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| VideoSourceError::InvalidApiData(format!("WBI密钥错误: {}", url)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Nav {
+    pub wbi_img: WbiImg,
+}
+
+#[derive(Debug, Deserialize)]
+struct WbiImg {
+    pub img_url: String,
+    pub sub_url: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::{fixture_routes, mock_api};
+    use super::WbiKey;
+
+    fn wbi_key() -> WbiKey {
+        WbiKey {
+            img_key: "7cd084941338484aae1ad9425b84077c".to_string(),
+            sub_key: "4932caff0ff746eab6f01bf08b70ac45".to_string(),
+        }
+    }
+
+    #[test]
+    fn sign_test() {
+        let key = wbi_key();
+        assert_eq!(key.mixin_key(), "ea1db124af3c7062474693fa704f4ff8");
+        let params = key.sign_at(
+            &[
+                ("foo", "114".to_string()),
+                ("bar", "514".to_string()),
+                ("zab", "1919810".to_string()),
+            ],
+            1702204169,
+        );
+        assert_eq!(
+            params.last().unwrap(),
+            &(
+                "w_rid".to_string(),
+                "8f6f2b5b3d485fe1886cec6a0be8c5d4".to_string()
+            )
+        );
+        let params = key.sign_at(
+            &[
+                ("mid", "2".to_string()),
+                ("keyword", "火柴 人!".to_string()),
+            ],
+            1702204169,
+        );
+        assert_eq!(params[0], ("keyword".to_string(), "火柴 人".to_string()));
+        assert_eq!(params.last().unwrap().1, "fa40719e5826680da7c9737a4d9a74ea");
+    }
+
+    #[tokio::test]
+    async fn request_wbi_key_test() {
+        let (_server, source) =
+            mock_api(fixture_routes(&[("/x/web-interface/nav", "nav.json")])).await;
+        assert_eq!(source.0.request_wbi_key().await.unwrap(), wbi_key());
+    }
+}
@@ -0,0 +1,481 @@
+use crate::error::VideoSourceError;
+use reqwest::Url;
+use std::cmp::Reverse;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// 可供选择的音视频流，如DASH中的一路视频或音频
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamFormat {
+    pub kind: StreamKind,
+    /// 来源中的清晰度/音质代码
+    pub id: i32,
+    /// 编码，如`avc1.640032`、`mp4a.40.2`
+    pub codecs: String,
+    /// 音频为0
+    pub width: u32,
+    /// 音频为0
+    pub height: u32,
+    /// 帧率，音频或未知时为`None`
+    pub frame_rate: Option<f64>,
+    /// 所需带宽，bps
+    pub bandwidth: u64,
+    /// 媒体类型，如`video/mp4`
+    pub mime_type: String,
+    pub url: Url,
+    /// 备用镜像
+    pub backup_urls: Vec<Url>,
+    /// 音质，视频或未知时为`None`
+    pub audio_quality: Option<AudioQuality>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Video,
+    Audio,
+}
+
+/// 视频编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    Avc,
+    Hevc,
+    Av1,
+}
+
+impl VideoCodec {
+    /// 按RFC 6381的编码字符串识别，如`avc1.640032`、`hev1.1.6.L150.90`
+    pub fn from_codecs(codecs: &str) -> Option<Self> {
+        match codecs.split('.').next()? {
+            "avc1" | "avc3" => Some(Self::Avc),
+            "hev1" | "hvc1" => Some(Self::Hevc),
+            "av01" => Some(Self::Av1),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = VideoSourceError;
+
+    fn from_str(codec: &str) -> Result<Self, Self::Err> {
+        match codec.trim().to_ascii_lowercase().as_str() {
+            "avc" | "h264" => Ok(Self::Avc),
+            "hevc" | "h265" => Ok(Self::Hevc),
+            "av1" => Ok(Self::Av1),
+            _ => Err(VideoSourceError::InvalidFormatSelection(codec.to_string())),
+        }
+    }
+}
+
+/// 音质，按从低到高排列，默认为192K
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AudioQuality {
+    K64,
+    K132,
+    #[default]
+    K192,
+    /// 杜比全景声
+    Dolby,
+    /// Hi-Res无损
+    HiRes,
+}
+
+impl Display for AudioQuality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::K64 => f.write_str("64K"),
+            Self::K132 => f.write_str("132K"),
+            Self::K192 => f.write_str("192K"),
+            Self::Dolby => f.write_str("杜比全景声"),
+            Self::HiRes => f.write_str("Hi-Res无损"),
+        }
+    }
+}
+
+impl FromStr for AudioQuality {
+    type Err = VideoSourceError;
+
+    fn from_str(quality: &str) -> Result<Self, Self::Err> {
+        match quality.trim().to_ascii_lowercase().as_str() {
+            "64k" => Ok(Self::K64),
+            "132k" => Ok(Self::K132),
+            "192k" => Ok(Self::K192),
+            "dolby" => Ok(Self::Dolby),
+            "hires" | "hi-res" | "flac" => Ok(Self::HiRes),
+            _ => Err(VideoSourceError::InvalidFormatSelection(
+                quality.to_string(),
+            )),
+        }
+    }
+}
+
+/// 请求的清晰度不存在时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QualityFallback {
+    /// 选择低于请求的最高清晰度
+    #[default]
+    Lower,
+    /// 选择高于请求的最低清晰度
+    Higher,
+    /// 返回错误
+    Fail,
+}
+
+impl QualityFallback {
+    /// 从可用的清晰度中选择，请求的清晰度存在时直接返回，没有可选的清晰度时为`None`
+    ///
+    /// 清晰度可以是来源的清晰度代码，也可以是`Quality::rank`
+    pub fn select<T, I>(self, requested: T, available: I) -> Option<T>
+    where
+        T: Ord,
+        I: IntoIterator<Item = T>,
+    {
+        let mut available = available.into_iter();
+        match self {
+            Self::Lower => available.filter(|quality| *quality <= requested).max(),
+            Self::Higher => available.filter(|quality| *quality >= requested).min(),
+            Self::Fail => available.find(|quality| *quality == requested),
+        }
+    }
+}
+
+impl FromStr for QualityFallback {
+    type Err = VideoSourceError;
+
+    fn from_str(fallback: &str) -> Result<Self, Self::Err> {
+        match fallback.trim().to_ascii_lowercase().as_str() {
+            "lower" => Ok(Self::Lower),
+            "higher" => Ok(Self::Higher),
+            "fail" => Ok(Self::Fail),
+            _ => Err(VideoSourceError::InvalidFormatSelection(
+                fallback.to_string(),
+            )),
+        }
+    }
+}
+
+impl StreamFormat {
+    pub fn codec(&self) -> Option<VideoCodec> {
+        VideoCodec::from_codecs(&self.codecs)
+    }
+
+    /// `url`及其备用镜像，用于`Downloader::download`
+    pub fn mirrors(&self) -> Vec<Url> {
+        std::iter::once(&self.url)
+            .chain(&self.backup_urls)
+            .cloned()
+            .collect()
+    }
+
+    /// 编码在`codecs`中的位置，越小越优先，不在其中时为`None`
+    fn codec_rank(&self, codecs: &[VideoCodec]) -> Option<usize> {
+        let codec = self.codec()?;
+        codecs.iter().position(|preferred| *preferred == codec)
+    }
+}
+
+/// 获取下载地址时的音视频流偏好
+///
+/// 先按`fallback`在可接受的视频流中确定清晰度，再在该清晰度中按`codecs`的顺序选择编码，
+/// 即清晰度优先于编码。可从字符串解析，如`<=1080p,hevc,avc,hires`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamPreference {
+    /// 可接受的视频编码，按优先顺序排列
+    ///
+    /// 请求的清晰度没有首选编码时依次尝试后面的编码，全部没有时返回错误。为空时不限编码，
+    /// 使用来源的默认编码
+    pub codecs: Vec<VideoCodec>,
+    /// 视频最大高度，`None`不限。更高的视频流不可接受，与`QualityFallback::Higher`一起使用时
+    /// 限制回退的上限。只用于分别提供音视频流的格式，如DASH
+    pub max_height: Option<u32>,
+    /// 音质，没有时选择低于它的最高音质，都高于它时选择最低音质
+    pub audio: AudioQuality,
+    /// 请求的清晰度不存在时的处理方式
+    pub fallback: QualityFallback,
+}
+
+impl StreamPreference {
+    /// 从同一清晰度的视频流中按编码偏好选择，超过`max_height`的视频流不可选
+    pub fn select_video<'a, I>(&self, formats: I) -> Option<&'a StreamFormat>
+    where
+        I: IntoIterator<Item = &'a StreamFormat>,
+    {
+        let mut formats = formats
+            .into_iter()
+            .filter(|format| self.max_height.is_none_or(|max| format.height <= max));
+        if self.codecs.is_empty() {
+            return formats.next();
+        }
+        formats
+            .filter_map(|format| Some((format.codec_rank(&self.codecs)?, format)))
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, format)| format)
+    }
+
+    /// 按音质偏好选择音频流，同一音质选择带宽较高者。未知音质视为最低
+    pub fn select_audio<'a, I>(&self, formats: I) -> Option<&'a StreamFormat>
+    where
+        I: IntoIterator<Item = &'a StreamFormat>,
+    {
+        let formats: Vec<_> = formats
+            .into_iter()
+            .filter(|format| format.kind == StreamKind::Audio)
+            .collect();
+        formats
+            .iter()
+            .filter(|format| format.audio_quality <= Some(self.audio))
+            .max_by_key(|format| (format.audio_quality, format.bandwidth))
+            .or_else(|| {
+                formats
+                    .iter()
+                    .min_by_key(|format| (format.audio_quality, Reverse(format.bandwidth)))
+            })
+            .copied()
+    }
+}
+
+impl FromStr for StreamPreference {
+    type Err = VideoSourceError;
+
+    /// 以逗号分隔：`best`、`<=1080p`（或`1080p`）、`avc`/`hevc`/`av1`（可多个，按顺序优先）、
+    /// 音质如`192k`/`hires`、回退方式`lower`/`higher`/`fail`
+    ///
+    /// `best`表示最高音质，即可以选择杜比全景声与Hi-Res，同时指定音质时以指定的为准
+    fn from_str(selection: &str) -> Result<Self, Self::Err> {
+        let invalid = || VideoSourceError::InvalidFormatSelection(selection.to_string());
+        let mut preference = Self::default();
+        let mut best = false;
+        let mut audio = None;
+        for term in selection
+            .split(',')
+            .map(|term| term.trim().to_ascii_lowercase())
+            .filter(|term| !term.is_empty())
+        {
+            if term == "best" {
+                best = true;
+            } else if let Some(height) = term
+                .trim_start_matches("<=")
+                .strip_suffix('p')
+                .filter(|height| !height.is_empty())
+            {
+                preference.max_height = Some(height.parse().map_err(|_| invalid())?);
+            } else if let Ok(codec) = term.parse() {
+                preference.codecs.push(codec);
+            } else if let Ok(quality) = term.parse() {
+                audio = Some(quality);
+            } else {
+                preference.fallback = term.parse().map_err(|_| invalid())?;
+            }
+        }
+        preference.audio = match audio {
+            Some(audio) => audio,
+            None if best => AudioQuality::HiRes,
+            None => preference.audio,
+        };
+        Ok(preference)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AudioQuality, QualityFallback, StreamFormat, StreamKind, StreamPreference, VideoCodec,
+    };
+    use crate::error::VideoSourceError;
+    use reqwest::Url;
+
+    fn format(
+        kind: StreamKind,
+        id: i32,
+        codecs: &str,
+        height: u32,
+        bandwidth: u64,
+    ) -> StreamFormat {
+        StreamFormat {
+            kind,
+            id,
+            codecs: codecs.to_string(),
+            width: height * 16 / 9,
+            height,
+            frame_rate: None,
+            bandwidth,
+            mime_type: String::new(),
+            url: Url::parse(&format!("https://example.com/{}-{}.m4s", id, codecs)).unwrap(),
+            backup_urls: vec![],
+            audio_quality: None,
+        }
+    }
+
+    #[test]
+    fn stream_preference_parse_test() {
+        let preference: StreamPreference = "<=1080p, hevc,AVC,hires,higher".parse().unwrap();
+        assert_eq!(
+            preference,
+            StreamPreference {
+                codecs: vec![VideoCodec::Hevc, VideoCodec::Avc],
+                max_height: Some(1080),
+                audio: AudioQuality::HiRes,
+                fallback: QualityFallback::Higher,
+            }
+        );
+        assert_eq!(
+            "best".parse::<StreamPreference>().unwrap(),
+            StreamPreference {
+                audio: AudioQuality::HiRes,
+                ..StreamPreference::default()
+            }
+        );
+        assert_eq!(
+            "best,192k".parse::<StreamPreference>().unwrap().audio,
+            AudioQuality::K192
+        );
+
+        // `best`时选择Hi-Res音轨
+        let audio = |id: i32, quality: AudioQuality| StreamFormat {
+            audio_quality: Some(quality),
+            ..format(StreamKind::Audio, id, "mp4a.40.2", 0, 320)
+        };
+        let formats = vec![
+            audio(30280, AudioQuality::K192),
+            audio(30250, AudioQuality::Dolby),
+            audio(30251, AudioQuality::HiRes),
+        ];
+        let preference: StreamPreference = "best,<=1080p,hevc".parse().unwrap();
+        assert_eq!(
+            preference.select_audio(&formats).map(|format| format.id),
+            Some(30251)
+        );
+        let preference: StreamPreference = "<=1080p,hevc".parse().unwrap();
+        assert_eq!(
+            preference.select_audio(&formats).map(|format| format.id),
+            Some(30280)
+        );
+        let preference: StreamPreference = "720p,av1,64k".parse().unwrap();
+        assert_eq!(
+            (preference.max_height, preference.codecs, preference.audio),
+            (Some(720), vec![VideoCodec::Av1], AudioQuality::K64)
+        );
+
+        for invalid in ["vp9", "<=p", "abcp", "1080i", "noaudio"] {
+            assert!(matches!(
+                invalid.parse::<StreamPreference>(),
+                Err(VideoSourceError::InvalidFormatSelection(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn stream_preference_test() {
+        let formats = vec![
+            format(StreamKind::Video, 80, "avc1.640032", 1080, 1500),
+            format(StreamKind::Video, 80, "hev1.1.6.L150.90", 1080, 700),
+            format(StreamKind::Video, 80, "av01.0.08M.08", 1080, 600),
+        ];
+        let select = |codecs: Vec<VideoCodec>| {
+            StreamPreference {
+                codecs,
+                ..StreamPreference::default()
+            }
+            .select_video(&formats)
+            .and_then(StreamFormat::codec)
+        };
+        assert_eq!(select(vec![]), Some(VideoCodec::Avc));
+        assert_eq!(
+            select(vec![VideoCodec::Hevc, VideoCodec::Avc]),
+            Some(VideoCodec::Hevc)
+        );
+        // 没有首选编码时依次回退
+        let formats = &formats[..1];
+        let preference = StreamPreference {
+            codecs: vec![VideoCodec::Hevc, VideoCodec::Avc],
+            ..StreamPreference::default()
+        };
+        assert_eq!(preference.select_video(formats), Some(&formats[0]));
+        let preference = StreamPreference {
+            codecs: vec![VideoCodec::Av1, VideoCodec::Hevc],
+            ..StreamPreference::default()
+        };
+        assert_eq!(preference.select_video(formats), None);
+
+        // 超过最大高度的视频流不可选
+        let preference = StreamPreference {
+            max_height: Some(720),
+            ..StreamPreference::default()
+        };
+        assert_eq!(preference.select_video(formats), None);
+        let preference = StreamPreference {
+            max_height: Some(1080),
+            ..preference
+        };
+        assert_eq!(preference.select_video(formats), Some(&formats[0]));
+    }
+
+    #[test]
+    fn audio_quality_test() {
+        let audio = |quality, bandwidth| StreamFormat {
+            audio_quality: Some(quality),
+            ..format(StreamKind::Audio, 0, "mp4a.40.2", 0, bandwidth)
+        };
+        let formats = vec![
+            audio(AudioQuality::K192, 319000),
+            audio(AudioQuality::K64, 67000),
+            audio(AudioQuality::HiRes, 1700000),
+            audio(AudioQuality::K132, 132000),
+            format(StreamKind::Video, 80, "avc1.640032", 1080, 1500),
+        ];
+        let select = |audio: AudioQuality| {
+            StreamPreference {
+                audio,
+                ..StreamPreference::default()
+            }
+            .select_audio(&formats)
+            .and_then(|format| format.audio_quality)
+        };
+        assert_eq!(select(AudioQuality::default()), Some(AudioQuality::K192));
+        assert_eq!(select(AudioQuality::K132), Some(AudioQuality::K132));
+        assert_eq!(select(AudioQuality::HiRes), Some(AudioQuality::HiRes));
+        // 没有杜比全景声时选择较低的最高音质
+        assert_eq!(select(AudioQuality::Dolby), Some(AudioQuality::K192));
+        let formats = &formats[2..];
+        let preference = StreamPreference {
+            audio: AudioQuality::K64,
+            ..StreamPreference::default()
+        };
+        // 都高于请求的音质时选择最低音质
+        assert_eq!(
+            preference.select_audio(formats).unwrap().audio_quality,
+            Some(AudioQuality::K132)
+        );
+
+        assert_eq!(
+            "Hi-Res".parse::<AudioQuality>().unwrap(),
+            AudioQuality::HiRes
+        );
+        assert_eq!("192k".parse::<AudioQuality>().unwrap(), AudioQuality::K192);
+        assert!("320k".parse::<AudioQuality>().is_err());
+        assert_eq!(AudioQuality::Dolby.to_string(), "杜比全景声");
+    }
+
+    #[test]
+    fn quality_fallback_test() {
+        let available = [16, 32, 64, 80];
+        assert_eq!(QualityFallback::Lower.select(64, available), Some(64));
+        assert_eq!(QualityFallback::Lower.select(112, available), Some(80));
+        assert_eq!(QualityFallback::Lower.select(6, available), None);
+        assert_eq!(QualityFallback::Higher.select(74, available), Some(80));
+        assert_eq!(QualityFallback::Higher.select(6, available), Some(16));
+        assert_eq!(QualityFallback::Higher.select(112, available), None);
+        assert_eq!(QualityFallback::Fail.select(32, available), Some(32));
+        assert_eq!(QualityFallback::Fail.select(74, available), None);
+        assert_eq!(QualityFallback::default(), QualityFallback::Lower);
+        assert_eq!(
+            "Higher".parse::<QualityFallback>().unwrap(),
+            QualityFallback::Higher
+        );
+        assert!(matches!(
+            "nearest".parse::<QualityFallback>(),
+            Err(VideoSourceError::InvalidFormatSelection(_))
+        ));
+    }
+}
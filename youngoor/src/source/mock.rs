@@ -0,0 +1,284 @@
+//! 测试用的本地HTTP服务器
+#![allow(dead_code)]
+
+use reqwest::Url;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug)]
+pub(crate) struct MockRequest {
+    pub method: String,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query(&self, name: &str) -> Option<String> {
+        self.url
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// 只发送前若干字节，之后保持连接不再发送
+    pub stall_after: Option<usize>,
+    /// 等待一段时间后再响应
+    pub delay: Option<Duration>,
+    /// 不响应，直接断开连接
+    pub disconnect: bool,
+}
+
+impl MockResponse {
+    pub fn json(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.into().into_bytes(),
+            stall_after: None,
+            delay: None,
+            disconnect: false,
+        }
+    }
+
+    pub fn bytes(content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body,
+            stall_after: None,
+            delay: None,
+            disconnect: false,
+        }
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        Self {
+            status: 302,
+            headers: vec![("Location".into(), location.into())],
+            body: vec![],
+            stall_after: None,
+            delay: None,
+            disconnect: false,
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+            stall_after: None,
+            delay: None,
+            disconnect: false,
+        }
+    }
+
+    /// 收到请求后直接断开连接，模拟网络错误
+    pub fn disconnect() -> Self {
+        Self {
+            disconnect: true,
+            ..Self::status(200)
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_stall(mut self, after: usize) -> Self {
+        self.stall_after = Some(after);
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+/// 按路径分发请求，未列出的路径返回404
+#[derive(Default)]
+pub(crate) struct MockRoutes {
+    routes: Vec<(String, Box<Handler>)>,
+}
+
+impl MockRoutes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由`handler`处理`path`的请求，同一路径以先添加的为准
+    pub fn route<F>(mut self, path: &str, handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        self.routes.push((path.to_string(), Box::new(handler)));
+        self
+    }
+
+    /// `path`总是返回`body`
+    pub fn json(self, path: &str, body: impl Into<String>) -> Self {
+        let body = body.into();
+        self.route(path, move |_| MockResponse::json(body.clone()))
+    }
+
+    pub async fn start(self) -> MockServer {
+        MockServer::start(move |request| {
+            match self
+                .routes
+                .iter()
+                .find(|(path, _)| path == request.url.path())
+            {
+                Some((_, handler)) => handler(request),
+                None => MockResponse::status(404),
+            }
+        })
+        .await
+    }
+}
+
+/// 每个连接只处理一个请求，响应后关闭连接
+pub(crate) struct MockServer {
+    addr: SocketAddr,
+    /// 正在处理的请求数
+    active: Arc<AtomicUsize>,
+    /// 同时处理的最大请求数
+    peak: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler: Arc<Handler> = Arc::new(handler);
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (counter, max) = (active.clone(), peak.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let (counter, max) = (counter.clone(), max.clone());
+                tokio::spawn(async move {
+                    let _ = Self::serve(stream, addr, handler, &counter, &max).await;
+                });
+            }
+        });
+        Self { addr, active, peak }
+    }
+
+    /// 同时处理的最大请求数，从收到请求起到开始响应为止
+    pub fn peak_requests(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    /// 重新开始统计`peak_requests`
+    pub fn reset_peak(&self) {
+        self.peak
+            .store(self.active.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        Url::parse(&format!("http://{}{}", self.addr, path)).unwrap()
+    }
+
+    async fn serve(
+        mut stream: TcpStream,
+        addr: SocketAddr,
+        handler: Arc<Handler>,
+        active: &AtomicUsize,
+        peak: &AtomicUsize,
+    ) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        let header_end = loop {
+            let mut chunk = [0u8; 1024];
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break position;
+            }
+        };
+        let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let target = request_line.next().unwrap_or("/");
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let content_length = headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = buffer[header_end + 4..].to_vec();
+        while body.len() < content_length {
+            let mut chunk = [0u8; 1024];
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..read]);
+        }
+        let request = MockRequest {
+            method,
+            url: Url::parse(&format!("http://{}{}", addr, target)).unwrap(),
+            headers,
+            body,
+        };
+        // 在开始响应前结束计数，客户端收到响应后发出的请求不会与本次重叠
+        let count = active.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(count, Ordering::SeqCst);
+        let response = handler(&request);
+        if let Some(delay) = response.delay {
+            tokio::time::sleep(delay).await;
+        }
+        active.fetch_sub(1, Ordering::SeqCst);
+        if response.disconnect {
+            return Ok(());
+        }
+        let mut head = format!("HTTP/1.1 {} MOCK\r\n", response.status);
+        for (key, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            response.body.len()
+        ));
+        stream.write_all(head.as_bytes()).await?;
+        if let Some(after) = response.stall_after {
+            stream.write_all(&response.body[..after]).await?;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            return Ok(());
+        }
+        stream.write_all(&response.body).await?;
+        stream.shutdown().await
+    }
+}
@@ -1,26 +1,230 @@
 pub mod bilibili;
+mod format;
+#[cfg(test)]
+pub(crate) mod mock;
+mod part;
+mod quality;
+mod resolve;
+
+pub use format::{
+    AudioQuality, QualityFallback, StreamFormat, StreamKind, StreamPreference, VideoCodec,
+};
+pub use part::PartSelection;
+pub use quality::{AccountTier, Quality};
+pub use resolve::{resolve_entries, ResolveOptions};
 
+use crate::cookie::{CookieJar, CookieWarning};
 use crate::error::VideoSourceError;
+use futures::future::{self, BoxFuture};
 use futures::stream::BoxStream;
 use reqwest::Url;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub type Result<T> = std::result::Result<T, VideoSourceError>;
 pub type VideoInfoStream<'a> = BoxStream<'a, Result<VideoInfo>>;
+pub type VideoEntryStream<'a> = BoxStream<'a, Result<VideoEntry>>;
 
-pub trait VideoSource {
+pub trait VideoSource: Sync {
     fn pretty_name(&self) -> &'static str;
+    /// 列出链接中的条目，只请求标题等元数据，不获取音视频流。单个条目的错误以
+    /// `Err(VideoSourceError::Entry)`返回，不会中断后续条目
+    ///
+    /// 列表中的条目可能只包含列表本身提供的信息，需经`expand_entry`展开
+    fn entries(&self, url: &Url, parts: &PartSelection) -> Result<VideoEntryStream<'_>>;
+    /// 展开`entries`返回的条目，如列表中的视频展开为各分P，不需要展开时返回`None`
+    ///
+    /// 展开需要请求详情，由`video_list`按`resolve_options`与获取音视频流一同并发进行
+    fn expand_entry<'a>(
+        &'a self,
+        _entry: &'a VideoEntry,
+    ) -> Option<BoxFuture<'a, Result<Vec<VideoEntry>>>> {
+        None
+    }
+    /// 获取单个条目的音视频流。地址会过期，应在下载前获取
+    fn resolve_entry<'a>(
+        &'a self,
+        entry: &'a VideoEntry,
+        video_type: VideoType,
+        quality: &'a Quality,
+        preference: &'a StreamPreference,
+    ) -> BoxFuture<'a, Result<VideoInfo>>;
+    /// 列出条目并按`resolve_options`并发获取音视频流，按列表顺序返回。单个条目失败时以
+    /// `Err(VideoSourceError::Entry)`返回，不会中断后续条目
     fn video_list(
         &self,
         url: &Url,
         video_type: VideoType,
-        dimension: i32,
-    ) -> Result<VideoInfoStream<'_>>;
+        quality: &Quality,
+        preference: &StreamPreference,
+        parts: &PartSelection,
+    ) -> Result<VideoInfoStream<'_>> {
+        let entries = self.entries(url, parts)?;
+        Ok(resolve_entries(
+            self,
+            entries,
+            video_type,
+            quality,
+            preference,
+            &self.resolve_options(),
+        ))
+    }
+    /// 获取音视频流时的并发数与请求间隔
+    fn resolve_options(&self) -> ResolveOptions {
+        ResolveOptions::default()
+    }
     fn valid(&self, url: &Url) -> bool;
+    /// 将短链接等间接地址解析为`valid`可识别的地址，默认原样返回
+    fn resolve<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<Url>> {
+        Box::pin(future::ready(Ok(url.clone())))
+    }
 
     fn set_token(&mut self, token: String);
     fn token(&self) -> Option<&str>;
+    /// 带有域名、路径与过期时间的Cookie，不使用Cookie的来源为`None`
+    fn cookies(&self) -> Option<&CookieJar> {
+        None
+    }
+    /// 导入Cookie，返回缺失或已过期的登录Cookie。不使用Cookie的来源忽略导入
+    fn import_cookies(&mut self, _cookies: CookieJar) -> Vec<CookieWarning> {
+        Vec::new()
+    }
+
+    /// 可选的清晰度，从低到高排列
+    fn qualities(&self) -> Vec<Quality>;
+}
+
+/// 列表中的条目，音视频流由`VideoSource::resolve_entry`按需获取
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoEntry {
+    pub pic: Option<Url>,
+    pub title: String,
+    /// 歌词（LRC）地址
+    pub lyric: Option<Url>,
+    pub metadata: VideoMetadata,
+    /// 来源获取音视频流所需的定位信息
+    pub key: EntryKey,
+}
+
+impl VideoEntry {
+    /// 标识条目的ID，用于错误信息，没有时为`key`
+    pub fn id(&self) -> String {
+        match &self.metadata.id {
+            Some(id) => id.clone(),
+            None => self.key.to_string(),
+        }
+    }
+
+    /// 不含音视频流的`VideoInfo`，由来源按请求的参数填充其余字段
+    pub fn into_video(
+        self,
+        video_type: VideoType,
+        quality: &Quality,
+        preference: &StreamPreference,
+    ) -> VideoInfo {
+        VideoInfo {
+            pic: self.pic.clone(),
+            title: self.title.clone(),
+            video: vec![],
+            audio: vec![],
+            lyric: self.lyric.clone(),
+            metadata: self.metadata.clone(),
+            formats: vec![],
+            quality: None,
+            audio_quality: None,
+            backup_urls: HashMap::new(),
+            expires: None,
+            handle: ResolveHandle {
+                entry: self,
+                video_type,
+                quality: quality.clone(),
+                preference: preference.clone(),
+            },
+        }
+    }
+}
+
+/// 条目的定位信息，类型由来源决定，其他代码不应依赖其内容
+///
+/// 来源用`new`保存自己的定位类型，用`get`按同一类型取回
+#[derive(Clone)]
+pub struct EntryKey(Arc<dyn KeyValue>);
+
+impl EntryKey {
+    pub fn new<T>(key: T) -> Self
+    where
+        T: Any + Debug + PartialEq + Send + Sync,
+    {
+        Self(Arc::new(key))
+    }
 
-    fn dimension(&self) -> Vec<(i32, String)>;
+    /// 类型为`T`时返回定位信息
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for EntryKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_key(other.0.as_ref())
+    }
+}
+
+impl Eq for EntryKey {}
+
+impl Debug for EntryKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Display for EntryKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// `EntryKey`中可比较的值
+trait KeyValue: Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn eq_key(&self, other: &dyn KeyValue) -> bool;
+}
+
+impl<T> KeyValue for T
+where
+    T: Any + Debug + PartialEq + Send + Sync,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_key(&self, other: &dyn KeyValue) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+/// 重新获取同一条目音视频流所需的信息，无需再次解析原链接
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveHandle {
+    pub entry: VideoEntry,
+    pub video_type: VideoType,
+    pub quality: Quality,
+    pub preference: StreamPreference,
+}
+
+impl ResolveHandle {
+    /// 用`source`重新获取音视频流，`source`应为最初获取它的来源
+    pub fn resolve<'a>(&'a self, source: &'a dyn VideoSource) -> BoxFuture<'a, Result<VideoInfo>> {
+        source.resolve_entry(
+            &self.entry,
+            self.video_type,
+            &self.quality,
+            &self.preference,
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -29,6 +233,76 @@ pub struct VideoInfo {
     pub title: String,
     pub video: Vec<Url>,
     pub audio: Vec<Url>,
+    /// 歌词（LRC）地址
+    pub lyric: Option<Url>,
+    pub metadata: VideoMetadata,
+    /// 全部可选的音视频流，`video`和`audio`为默认选择的结果。来源不支持时为空
+    pub formats: Vec<StreamFormat>,
+    /// `video`的实际清晰度，来源不支持时为`None`
+    pub quality: Option<Quality>,
+    /// `audio`的实际音质，来源不支持时为`None`
+    pub audio_quality: Option<AudioQuality>,
+    /// `video`、`audio`中地址的备用镜像
+    pub backup_urls: HashMap<Url, Vec<Url>>,
+    /// 地址的过期时间（Unix时间戳，秒），来源不提供时为`None`
+    pub expires: Option<i64>,
+    /// 用于地址过期后重新获取
+    pub handle: ResolveHandle,
+}
+
+impl VideoInfo {
+    /// 地址将在`margin`内过期，没有过期时间时为`false`
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.expires
+            .is_some_and(|expires| expires <= (now + margin).as_secs() as i64)
+    }
+
+    /// 重新获取音视频流，见`ResolveHandle::resolve`
+    pub fn refresh<'a>(&'a self, source: &'a dyn VideoSource) -> BoxFuture<'a, Result<VideoInfo>> {
+        self.handle.resolve(source)
+    }
+
+    /// `url`及其备用镜像，用于`Downloader::download`
+    pub fn mirrors(&self, url: &Url) -> Vec<Url> {
+        std::iter::once(url)
+            .chain(self.backup_urls.get(url).into_iter().flatten())
+            .cloned()
+            .collect()
+    }
+}
+
+/// 视频元数据，来源不提供的字段为空
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VideoMetadata {
+    /// 来源中的ID，如BV号、`ep`开头的剧集分集ID
+    pub id: Option<String>,
+    /// 分P序号，从1开始
+    pub part: Option<u32>,
+    pub duration: Option<Duration>,
+    pub resolution: Option<Resolution>,
+    /// UP主
+    pub uploader: Option<String>,
+    /// 发布时间（Unix时间戳，秒）
+    pub published: Option<i64>,
+    /// 简介
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// 所属剧集、合集或课程的标题
+    pub series: Option<String>,
+    /// 在剧集、合集或课程中的序号，从1开始
+    pub episode: Option<u32>,
+    /// 所属合集的链接，可用`VideoSource::video_list`列出合集中的全部视频
+    pub series_url: Option<Url>,
+}
+
+/// 分辨率，已按旋转方向调整
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
@@ -37,7 +311,60 @@ pub enum VideoType {
     MP4,
 }
 
+/// 从分享文本中提取所有`http`/`https`链接
+///
+/// 链接在空白或非ASCII字符处截断，如`【标题】 https://b23.tv/xxxx`
+pub fn extract_urls(text: &str) -> Vec<Url> {
+    let mut urls = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("http") {
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| !c.is_ascii_graphic() || matches!(c, '"' | '\'' | '<' | '>'))
+            .unwrap_or(candidate.len());
+        let candidate = candidate[..end].trim_end_matches(['.', ',', ')', ']']);
+        if candidate.starts_with("http://") || candidate.starts_with("https://") {
+            if let Ok(url) = Url::parse(candidate) {
+                urls.push(url);
+            }
+        }
+        rest = &rest[start + end.max(4)..];
+    }
+    urls
+}
+
+/// 在分享文本中查找第一个可被某个视频源处理的链接
+///
+/// 返回该视频源和经`VideoSource::resolve`解析后的地址。解析失败时继续尝试其他链接与视频源，
+/// 都不能处理时返回第一个解析错误
+pub async fn find_source<'a>(
+    sources: &'a [Box<dyn VideoSource>],
+    text: &str,
+) -> Result<(&'a dyn VideoSource, Url)> {
+    let urls = extract_urls(text);
+    let mut error = None;
+    for url in &urls {
+        for source in sources {
+            match source.resolve(url).await {
+                Ok(resolved) if source.valid(&resolved) => return Ok((source.as_ref(), resolved)),
+                Ok(_) => {}
+                Err(resolve_error) => {
+                    error.get_or_insert(resolve_error);
+                }
+            }
+        }
+    }
+    if let Some(error) = error {
+        return Err(error);
+    }
+    match urls.into_iter().next() {
+        Some(url) => Err(VideoSourceError::InvalidUrl(url)),
+        None => Err(VideoSourceError::UrlNotFound(text.to_string())),
+    }
+}
+
 #[macro_export]
+#[allow(clippy::crate_in_macro_def)]
 macro_rules! video_sources {
     [$($source:ty),*] => {{
         let mut sources = ::std::vec::Vec::<::std::boxed::Box::<dyn crate::source::VideoSource>>::new();
@@ -48,10 +375,16 @@ macro_rules! video_sources {
 
 #[cfg(test)]
 mod test {
-    use super::{Result, VideoInfoStream, VideoSource, VideoType};
+    use super::{
+        bilibili::BilibiliSource, extract_urls, find_source, PartSelection, Quality, Result,
+        StreamPreference, VideoEntry, VideoEntryStream, VideoInfo, VideoSource, VideoType,
+    };
+    use crate::error::VideoSourceError;
+    use futures::future::BoxFuture;
     use reqwest::Url;
 
     #[test]
+    #[allow(clippy::vec_init_then_push)]
     fn video_sources_test() {
         #[derive(Default)]
         struct VideoSource1;
@@ -60,12 +393,17 @@ mod test {
                 "source1"
             }
 
-            fn video_list(
-                &self,
-                _url: &Url,
+            fn entries(&self, _url: &Url, _parts: &PartSelection) -> Result<VideoEntryStream<'_>> {
+                unimplemented!()
+            }
+
+            fn resolve_entry<'a>(
+                &'a self,
+                _entry: &'a VideoEntry,
                 _video_type: VideoType,
-                _dimension: i32,
-            ) -> Result<VideoInfoStream<'_>> {
+                _quality: &'a Quality,
+                _preference: &'a StreamPreference,
+            ) -> BoxFuture<'a, Result<VideoInfo>> {
                 unimplemented!()
             }
 
@@ -81,7 +419,7 @@ mod test {
                 unimplemented!()
             }
 
-            fn dimension(&self) -> Vec<(i32, String)> {
+            fn qualities(&self) -> Vec<Quality> {
                 unimplemented!()
             }
         }
@@ -92,12 +430,17 @@ mod test {
                 "source2"
             }
 
-            fn video_list(
-                &self,
-                _url: &Url,
+            fn entries(&self, _url: &Url, _parts: &PartSelection) -> Result<VideoEntryStream<'_>> {
+                unimplemented!()
+            }
+
+            fn resolve_entry<'a>(
+                &'a self,
+                _entry: &'a VideoEntry,
                 _video_type: VideoType,
-                _dimension: i32,
-            ) -> Result<VideoInfoStream<'_>> {
+                _quality: &'a Quality,
+                _preference: &'a StreamPreference,
+            ) -> BoxFuture<'a, Result<VideoInfo>> {
                 unimplemented!()
             }
 
@@ -113,7 +456,7 @@ mod test {
                 unimplemented!()
             }
 
-            fn dimension(&self) -> Vec<(i32, String)> {
+            fn qualities(&self) -> Vec<Quality> {
                 unimplemented!()
             }
         }
@@ -122,4 +465,112 @@ mod test {
         assert_eq!(sources[0].pretty_name(), "source1");
         assert_eq!(sources[1].pretty_name(), "source2");
     }
+
+    #[test]
+    fn extract_urls_test() {
+        assert_eq!(
+            extract_urls("【【4K】火柴人VS动画师-哔哩哔哩】 https://b23.tv/BV1ex411J7GE"),
+            vec![Url::parse("https://b23.tv/BV1ex411J7GE").unwrap()]
+        );
+        assert_eq!(
+            extract_urls("看这个https://b23.tv/abcd，还有(https://www.bilibili.com/video/BV1y7411Q7Eq?p=2)。"),
+            vec![
+                Url::parse("https://b23.tv/abcd").unwrap(),
+                Url::parse("https://www.bilibili.com/video/BV1y7411Q7Eq?p=2").unwrap(),
+            ]
+        );
+        assert!(extract_urls("没有链接 http: https").is_empty());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::vec_init_then_push)]
+    async fn find_source_test() {
+        let sources = crate::video_sources![BilibiliSource];
+        let (source, url) = find_source(
+            &sources,
+            "【标题】 https://www.bilibili.com/bangumi/play/ep327884 分享",
+        )
+        .await
+        .unwrap();
+        assert_eq!(source.pretty_name(), "bilibili");
+        assert_eq!(
+            url,
+            Url::parse("https://www.bilibili.com/bangumi/play/ep327884").unwrap()
+        );
+        assert!(matches!(
+            find_source(&sources, "https://example.com/video").await,
+            Err(VideoSourceError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            find_source(&sources, "没有链接").await,
+            Err(VideoSourceError::UrlNotFound(_))
+        ));
+    }
+
+    /// 解析任何链接都失败的视频源，如网络错误
+    #[derive(Default)]
+    struct FailingSource;
+
+    impl VideoSource for FailingSource {
+        fn pretty_name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn entries(&self, _url: &Url, _parts: &PartSelection) -> Result<VideoEntryStream<'_>> {
+            unimplemented!()
+        }
+
+        fn resolve_entry<'a>(
+            &'a self,
+            _entry: &'a VideoEntry,
+            _video_type: VideoType,
+            _quality: &'a Quality,
+            _preference: &'a StreamPreference,
+        ) -> BoxFuture<'a, Result<VideoInfo>> {
+            unimplemented!()
+        }
+
+        fn valid(&self, _: &Url) -> bool {
+            false
+        }
+
+        fn resolve<'a>(&'a self, _url: &'a Url) -> BoxFuture<'a, Result<Url>> {
+            Box::pin(async { Err(VideoSourceError::RequestError("连接失败".to_string())) })
+        }
+
+        fn set_token(&mut self, _token: String) {
+            unimplemented!()
+        }
+
+        fn token(&self) -> Option<&str> {
+            unimplemented!()
+        }
+
+        fn qualities(&self) -> Vec<Quality> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::vec_init_then_push)]
+    async fn find_source_error_test() {
+        // 解析错误不影响其他视频源与其他链接
+        let sources = crate::video_sources![FailingSource, BilibiliSource];
+        let (source, url) = find_source(
+            &sources,
+            "https://example.com/video https://www.bilibili.com/video/BV1ex411J7GE",
+        )
+        .await
+        .unwrap();
+        assert_eq!(source.pretty_name(), "bilibili");
+        assert_eq!(
+            url,
+            Url::parse("https://www.bilibili.com/video/BV1ex411J7GE").unwrap()
+        );
+        // 都不能处理时返回解析错误
+        assert!(matches!(
+            find_source(&sources, "https://example.com/video").await,
+            Err(VideoSourceError::RequestError(_))
+        ));
+    }
 }
@@ -0,0 +1,150 @@
+use crate::error::VideoSourceError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// 分P选择，如`1-3,7,10-`，序号从1开始
+///
+/// 为空时选择全部分P
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartSelection(Vec<PartRange>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PartRange {
+    start: u32,
+    /// `None`表示直到最后一P
+    end: Option<u32>,
+}
+
+impl PartSelection {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn single(part: u32) -> Self {
+        Self(vec![PartRange {
+            start: part,
+            end: Some(part),
+        }])
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 选择的最后一P，选择直到最后一P时为`None`，用于提前结束分页请求
+    pub fn last(&self) -> Option<u32> {
+        if self.is_all() {
+            return None;
+        }
+        self.0
+            .iter()
+            .map(|range| range.end)
+            .try_fold(0, |last, end| end.map(|end| last.max(end)))
+    }
+
+    pub fn contains(&self, part: u32) -> bool {
+        self.is_all()
+            || self
+                .0
+                .iter()
+                .any(|range| part >= range.start && range.end.is_none_or(|end| part <= end))
+    }
+}
+
+impl FromStr for PartSelection {
+    type Err = VideoSourceError;
+
+    fn from_str(selection: &str) -> Result<Self, Self::Err> {
+        let invalid = || VideoSourceError::InvalidPartSelection(selection.to_string());
+        let parse_part = |part: &str| match part.trim().parse::<u32>() {
+            Ok(part) if part > 0 => Ok(part),
+            _ => Err(invalid()),
+        };
+        let mut ranges = Vec::new();
+        for range in selection
+            .split(',')
+            .filter(|range| !range.trim().is_empty())
+        {
+            let range = match range.split_once('-') {
+                Some((start, end)) => {
+                    let start = if start.trim().is_empty() {
+                        1
+                    } else {
+                        parse_part(start)?
+                    };
+                    let end = if end.trim().is_empty() {
+                        None
+                    } else {
+                        Some(parse_part(end)?)
+                    };
+                    if end.is_some_and(|end| end < start) {
+                        return Err(invalid());
+                    }
+                    PartRange { start, end }
+                }
+                None => {
+                    let part = parse_part(range)?;
+                    PartRange {
+                        start: part,
+                        end: Some(part),
+                    }
+                }
+            };
+            ranges.push(range);
+        }
+        Ok(Self(ranges))
+    }
+}
+
+impl Display for PartSelection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<String> = self
+            .0
+            .iter()
+            .map(|range| match range.end {
+                Some(end) if end == range.start => end.to_string(),
+                Some(end) => format!("{}-{}", range.start, end),
+                None => format!("{}-", range.start),
+            })
+            .collect();
+        f.write_str(&ranges.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PartSelection;
+    use crate::error::VideoSourceError;
+
+    #[test]
+    fn part_selection_test() {
+        let selection: PartSelection = "1-3, 7,10-".parse().unwrap();
+        assert!((1..=3).all(|part| selection.contains(part)));
+        assert!(!selection.contains(4));
+        assert!(selection.contains(7));
+        assert!(!selection.contains(9));
+        assert!(selection.contains(10));
+        assert!(selection.contains(200));
+        assert_eq!(selection.to_string(), "1-3,7,10-");
+        assert_eq!(selection.last(), None);
+        assert_eq!("7,1-3".parse::<PartSelection>().unwrap().last(), Some(7));
+
+        let selection: PartSelection = "-2".parse().unwrap();
+        assert!(selection.contains(1) && selection.contains(2) && !selection.contains(3));
+
+        let selection: PartSelection = "".parse().unwrap();
+        assert!(selection.is_all());
+        assert!(selection.contains(1));
+        assert_eq!(selection.last(), None);
+
+        assert!(PartSelection::single(5).contains(5));
+        assert!(!PartSelection::single(5).contains(4));
+
+        for invalid in ["0", "3-1", "a", "1-b", "1--2"] {
+            assert!(matches!(
+                invalid.parse::<PartSelection>(),
+                Err(VideoSourceError::InvalidPartSelection(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! 与来源无关的清晰度描述，各来源将其映射为自己的清晰度代码
+
+use super::QualityFallback;
+
+use std::fmt::{Display, Formatter};
+
+/// 观看所需的账号等级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AccountTier {
+    /// 无需登录
+    #[default]
+    Guest,
+    /// 需要登录
+    Member,
+    /// 需要大会员等付费会员
+    Premium,
+}
+
+/// 清晰度
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Quality {
+    /// 视频高度
+    pub height: u32,
+    /// 帧率，高帧率为60，其他为30
+    pub frame_rate: u32,
+    /// 同一分辨率下的高码率版本，如1080P+
+    pub high_bitrate: bool,
+    pub hdr: bool,
+    /// 杜比视界
+    pub dolby: bool,
+    pub tier: AccountTier,
+    /// 本地化名称，如`1080P 高清`
+    pub label: String,
+}
+
+impl Quality {
+    /// 30帧、普通码率、无需登录的清晰度
+    pub fn new(height: u32, label: &str) -> Self {
+        Self {
+            height,
+            frame_rate: 30,
+            high_bitrate: false,
+            hdr: false,
+            dolby: false,
+            tier: AccountTier::Guest,
+            label: label.to_string(),
+        }
+    }
+
+    /// 比较清晰度高低的键，不考虑账号等级与名称
+    pub fn rank(&self) -> (u32, u32, bool, bool, bool) {
+        (
+            self.height,
+            self.frame_rate,
+            self.high_bitrate,
+            self.hdr,
+            self.dolby,
+        )
+    }
+
+    /// 在来源的清晰度中查找与`self`对应的代码：不高于它的最高清晰度，都高于它时为最低清晰度
+    pub fn code_in<C, I>(&self, qualities: I) -> Option<C>
+    where
+        I: IntoIterator<Item = (C, Quality)>,
+    {
+        let ranks: Vec<_> = qualities
+            .into_iter()
+            .map(|(code, quality)| (quality.rank(), code))
+            .collect();
+        let rank = QualityFallback::Lower
+            .select(self.rank(), ranks.iter().map(|(rank, _)| *rank))
+            .or_else(|| {
+                QualityFallback::Higher.select(self.rank(), ranks.iter().map(|(rank, _)| *rank))
+            })?;
+        ranks
+            .into_iter()
+            .find(|(candidate, _)| *candidate == rank)
+            .map(|(_, code)| code)
+    }
+}
+
+impl Display for Quality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccountTier, Quality};
+
+    #[test]
+    fn quality_code_test() {
+        let qualities = vec![
+            (16, Quality::new(360, "360P")),
+            (64, Quality::new(720, "720P")),
+            (
+                74,
+                Quality {
+                    frame_rate: 60,
+                    tier: AccountTier::Premium,
+                    ..Quality::new(720, "720P60")
+                },
+            ),
+            (80, Quality::new(1080, "1080P")),
+        ];
+        // 名称与账号等级不影响匹配
+        assert_eq!(
+            Quality::new(720, "高清").code_in(qualities.clone()),
+            Some(64)
+        );
+        let p720f60 = Quality {
+            frame_rate: 60,
+            ..Quality::new(720, "")
+        };
+        assert_eq!(p720f60.code_in(qualities.clone()), Some(74));
+        assert!(p720f60.rank() > Quality::new(720, "").rank());
+        assert!(p720f60.rank() < Quality::new(1080, "").rank());
+        assert_eq!(
+            Quality::new(2160, "4K").code_in(qualities.clone()),
+            Some(80)
+        );
+        assert_eq!(
+            Quality::new(240, "240P").code_in(qualities.clone()),
+            Some(16)
+        );
+        assert_eq!(
+            Quality::new(240, "240P").code_in(Vec::<(i32, _)>::new()),
+            None
+        );
+        assert_eq!(Quality::new(1080, "1080P 高清").to_string(), "1080P 高清");
+    }
+}
@@ -0,0 +1,117 @@
+//! 并发获取条目的音视频流
+
+use super::{Quality, StreamPreference, VideoEntryStream, VideoInfoStream, VideoSource, VideoType};
+
+use futures::stream::{self, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::{sleep_until, Instant};
+
+/// 获取音视频流的选项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOptions {
+    /// 同时进行的请求数，为0时视为1
+    pub concurrency: usize,
+    /// 相邻两个请求开始的最小间隔，避免触发风控
+    pub interval: Duration,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// 并发展开`entries`中的条目并获取音视频流，按条目原本的顺序返回
+///
+/// 展开与获取音视频流的请求共用并发数与请求间隔，同时进行的请求不超过`concurrency`。
+/// 单个条目失败时以`Err(VideoSourceError::Entry)`返回，不会中断后续条目
+pub fn resolve_entries<'a, S>(
+    source: &'a S,
+    entries: VideoEntryStream<'a>,
+    video_type: VideoType,
+    quality: &Quality,
+    preference: &StreamPreference,
+    options: &ResolveOptions,
+) -> VideoInfoStream<'a>
+where
+    S: VideoSource + ?Sized,
+{
+    let (quality, preference) = (quality.clone(), preference.clone());
+    let concurrency = options.concurrency.max(1);
+    let limit = Arc::new(Semaphore::new(concurrency));
+    let throttle = Throttle::new(options.interval);
+    let (expand_limit, expand_throttle) = (limit.clone(), throttle.clone());
+    Box::pin(
+        entries
+            // 展开在获取音视频流的上游依次进行，不占用`buffered`的位置
+            .map(move |entry| {
+                let (limit, throttle) = (expand_limit.clone(), expand_throttle.clone());
+                let expanded = async move {
+                    let entry = entry?;
+                    let expanded = match source.expand_entry(&entry) {
+                        Some(expand) => {
+                            let _permit = limit.acquire().await.expect("信号量未关闭");
+                            throttle.wait().await;
+                            expand.await
+                        }
+                        None => Ok(vec![entry.clone()]),
+                    };
+                    expanded.map_err(|error| error.entry(entry.id(), entry.title.as_str()))
+                };
+                stream::once(expanded).flat_map(|expanded| {
+                    stream::iter(match expanded {
+                        Ok(entries) => entries.into_iter().map(Ok).collect(),
+                        Err(error) => vec![Err(error)],
+                    })
+                })
+            })
+            .flatten()
+            .map(move |entry| {
+                let (quality, preference) = (quality.clone(), preference.clone());
+                let (limit, throttle) = (limit.clone(), throttle.clone());
+                async move {
+                    let entry = entry?;
+                    let _permit = limit.acquire().await.expect("信号量未关闭");
+                    throttle.wait().await;
+                    source
+                        .resolve_entry(&entry, video_type, &quality, &preference)
+                        .await
+                        .map_err(|error| error.entry(entry.id(), entry.title.as_str()))
+                }
+            })
+            .buffered(concurrency),
+    )
+}
+
+/// 保证相邻两个请求开始的间隔
+#[derive(Clone)]
+struct Throttle {
+    interval: Duration,
+    /// 下一个请求最早的开始时间
+    next_start: Arc<Mutex<Instant>>,
+}
+
+impl Throttle {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_start: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// 预留下一个开始时间并等待，等待期间不持有锁，未被轮询的请求不会阻塞其他请求
+    async fn wait(&self) {
+        let start = {
+            let mut next_start = self.next_start.lock().unwrap();
+            let start = (*next_start).max(Instant::now());
+            *next_start = start + self.interval;
+            start
+        };
+        sleep_until(start).await;
+    }
+}